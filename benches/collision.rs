@@ -0,0 +1,172 @@
+//! Times player lasers against enemies for a growing number of both, comparing the
+//! game's spatial index with checking every laser against every enemy.
+//! `cargo bench --bench collision`
+
+use bevy::{prelude::*, sprite::collide_aabb::collide, utils::HashSet};
+use bevy_invaders::{
+    components::{
+        Enemy, EnemyKind, ExplosionToSpawn, FromPlayer, GravityAssist, Laser, PlayerId, SpriteSize,
+    },
+    player_laser_hit_enemy_system,
+    score::{ScoreEvent, ScoreReason},
+    spatial::{spatial_index_system, SpatialIndex},
+    telemetry::SystemTimings,
+    Despawned, EnemyCount, EnemyDestroyed, WorldBounds, CURVED_SHOT_MIN_ANGLE,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::prelude::*;
+
+const BODY_COUNTS: [usize; 4] = [10, 100, 1_000, 10_000];
+
+// as the game spawns them, sprite size times the sprite scale
+const ENEMY_SIZE: (f32, f32) = (46.5, 42.);
+const LASER_SIZE: (f32, f32) = (4.5, 27.);
+
+/// A candidate as (name, adds its systems to the app).
+type Candidate = (&'static str, fn(&mut App));
+
+const CANDIDATES: [Candidate; 2] = [
+    ("nested_loop", |app| {
+        app.add_system(nested_loop_hit_system);
+    }),
+    // rebuilding the index counts towards its time
+    ("spatial_index", |app| {
+        app.insert_resource(SpatialIndex::default())
+            .add_system_to_stage(CoreStage::PreUpdate, spatial_index_system)
+            .add_system(player_laser_hit_enemy_system);
+    }),
+];
+
+/// A headless app with `count` enemies and as many lasers scattered over the world.
+fn collision_app(count: usize, add_systems: fn(&mut App)) -> App {
+    let mut app = App::new();
+    app.insert_resource(WorldBounds::default())
+        .insert_resource(SystemTimings::default())
+        .insert_resource(EnemyCount(count as u32))
+        .insert_resource(Despawned::default())
+        .add_event::<EnemyDestroyed>()
+        .add_event::<ScoreEvent>();
+    add_systems(&mut app);
+    // run once empty so the systems are set up outside the timing
+    app.update();
+
+    // the same layout every run so the numbers compare
+    let mut rng = StdRng::seed_from_u64(0);
+    let half = WorldBounds::default().size / 2.;
+    let mut position = || {
+        Vec3::new(
+            rng.gen_range(-half.x..half.x),
+            rng.gen_range(-half.y..half.y),
+            0.,
+        )
+    };
+    for _ in 0..count {
+        let enemy = (
+            Transform::from_translation(position()),
+            SpriteSize::from(ENEMY_SIZE),
+            EnemyKind::Invader,
+            Enemy,
+        );
+        app.world.spawn(enemy);
+        let laser = (
+            Transform::from_translation(position()),
+            SpriteSize::from(LASER_SIZE),
+            FromPlayer(PlayerId(0)),
+            Laser,
+        );
+        app.world.spawn(laser);
+    }
+    app
+}
+
+type Lasers<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static SpriteSize,
+        &'static FromPlayer,
+        Option<&'static GravityAssist>,
+    ),
+    With<Laser>,
+>;
+
+/// How the game checked before the spatial index, every laser against every enemy.
+fn nested_loop_hit_system(
+    mut commands: Commands,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut enemy_destroyed: EventWriter<EnemyDestroyed>,
+    mut score_events: EventWriter<ScoreEvent>,
+    laser_query: Lasers,
+    enemy_query: Query<(Entity, &Transform, &SpriteSize, &EnemyKind), With<Enemy>>,
+) {
+    let mut despawned: HashSet<Entity> = HashSet::new();
+
+    for (laser_entity, laser_tf, laser_size, from_player, gravity_assist) in laser_query.iter() {
+        for (enemy_entity, enemy_tf, enemy_size, enemy_kind) in enemy_query.iter() {
+            if despawned.contains(&enemy_entity) || despawned.contains(&laser_entity) {
+                continue;
+            }
+            let collision = collide(
+                laser_tf.translation,
+                laser_size.0 * laser_tf.scale.truncate(),
+                enemy_tf.translation,
+                enemy_size.0 * enemy_tf.scale.truncate(),
+            );
+            if collision.is_none() {
+                continue;
+            }
+
+            commands.entity(enemy_entity).despawn();
+            commands.entity(laser_entity).despawn();
+            despawned.insert(enemy_entity);
+            despawned.insert(laser_entity);
+            enemy_count.0 -= 1;
+
+            score_events.send(ScoreEvent {
+                player: from_player.0,
+                reason: ScoreReason::Kill(*enemy_kind),
+                position: enemy_tf.translation,
+            });
+            if let Some(gravity_assist) = gravity_assist {
+                if gravity_assist.bend >= CURVED_SHOT_MIN_ANGLE {
+                    score_events.send(ScoreEvent {
+                        player: from_player.0,
+                        reason: ScoreReason::TrickShot,
+                        position: enemy_tf.translation,
+                    });
+                }
+            }
+            commands.spawn(ExplosionToSpawn(enemy_tf.translation));
+            enemy_destroyed.send(EnemyDestroyed {
+                kind: *enemy_kind,
+                position: enemy_tf.translation,
+            });
+        }
+    }
+}
+
+fn collision(c: &mut Criterion) {
+    let mut group = c.benchmark_group("laser_hit_enemy");
+    for count in BODY_COUNTS {
+        group.sample_size(if count >= 1_000 { 10 } else { 100 });
+        for (name, add_systems) in CANDIDATES {
+            // hits despawn what they hit, so every frame starts from a fresh world
+            group.bench_with_input(BenchmarkId::new(name, count), &count, |b, &count| {
+                b.iter_batched(
+                    || collision_app(count, add_systems),
+                    |mut app| {
+                        app.update();
+                        app
+                    },
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, collision);
+criterion_main!(benches);
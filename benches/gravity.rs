@@ -0,0 +1,132 @@
+//! Times one frame of gravity for a growing number of bodies, for each way of working
+//! it out. `cargo bench --bench gravity`
+
+use bevy::{math::Vec3Swizzles, prelude::*, utils::HashMap};
+use bevy_invaders::{
+    apply_gravitational_forces, components::GravitySource, telemetry::SystemTimings, WorldBounds,
+    EXTRA_GRAVITY, G, GRAVITY_SOURCE_MIN_DISTANCE,
+};
+use bevy_rapier2d::prelude::{ExternalForce, MassProperties, ReadMassProperties};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::prelude::*;
+use std::f32::consts::PI;
+
+const BODY_COUNTS: [usize; 4] = [10, 100, 1_000, 10_000];
+
+/// A candidate as (name, adds its systems to the app).
+type Candidate = (&'static str, fn(&mut App));
+
+const CANDIDATES: [Candidate; 2] = [
+    ("serial", |app| {
+        app.add_system(serial_gravity_system);
+    }),
+    ("parallel", |app| {
+        app.add_system(apply_gravitational_forces);
+    }),
+];
+
+/// How the game worked gravity out before it was spread over threads, one pair at a
+/// time with the forces gathered in a map.
+fn serial_gravity_system(
+    world_bounds: Res<WorldBounds>,
+    query: Query<(Entity, &Transform, &ReadMassProperties)>,
+    source_query: Query<(&Transform, &GravitySource)>,
+    mut force_query: Query<&mut ExternalForce>,
+) {
+    let mut forces: HashMap<Entity, Vec2> = HashMap::default();
+    let data: Vec<(Entity, Vec2, f32)> = query
+        .iter()
+        .map(|(entity, tf, mass_props)| (entity, tf.translation.xy(), mass_props.0.mass))
+        .collect();
+    for (entity, ..) in data.iter() {
+        forces.insert(*entity, Vec2::ZERO);
+    }
+
+    for (n_1, (entity_1, position_1, mass_1)) in data.clone().iter().enumerate() {
+        for (entity_2, position_2, mass_2) in data.iter().skip(n_1 + 1) {
+            let offset = world_bounds.offset(*position_2, *position_1);
+            let force = (mass_1 * mass_2 * G) / offset.length_squared();
+            let acute_angle = if offset.x == 0. {
+                PI / 2.
+            } else {
+                (offset.y.abs() / offset.x.abs()).atan()
+            };
+            let force_x = acute_angle.cos() * force * EXTRA_GRAVITY;
+            let force_y = acute_angle.sin() * force * EXTRA_GRAVITY;
+            let x_s = if offset.x.is_sign_positive() { -1. } else { 1. };
+            let y_s = if offset.y.is_sign_positive() { -1. } else { 1. };
+            let force_1 = Vec2::new(force_x * x_s, force_y * y_s);
+            forces
+                .entry(*entity_1)
+                .and_modify(|force| *force += force_1);
+            forces
+                .entry(*entity_2)
+                .and_modify(|force| *force -= force_1);
+        }
+    }
+
+    for (source_tf, source) in source_query.iter() {
+        let source_position = source_tf.translation.xy();
+        for (entity, position, mass) in data.iter() {
+            let offset = world_bounds.offset(*position, source_position);
+            let distance_squared = offset
+                .length_squared()
+                .max(GRAVITY_SOURCE_MIN_DISTANCE.powi(2));
+            let force = (mass * source.mass * G) / distance_squared * EXTRA_GRAVITY;
+            forces
+                .entry(*entity)
+                .and_modify(|f| *f += offset.normalize_or_zero() * force);
+        }
+    }
+
+    for (entity, force) in forces.drain() {
+        if let Ok(mut ext_force) = force_query.get_mut(entity) {
+            ext_force.force = force;
+        }
+    }
+}
+
+/// A headless app with nothing in it but `bodies` bodies scattered over the world.
+fn gravity_app(bodies: usize, add_systems: fn(&mut App)) -> App {
+    let mut app = App::new();
+    app.insert_resource(WorldBounds::default())
+        .insert_resource(SystemTimings::default());
+    add_systems(&mut app);
+
+    // the same bodies every run so the numbers compare
+    let mut rng = StdRng::seed_from_u64(0);
+    let half = WorldBounds::default().size / 2.;
+    for _ in 0..bodies {
+        let position = Vec2::new(
+            rng.gen_range(-half.x..half.x),
+            rng.gen_range(-half.y..half.y),
+        );
+        app.world.spawn((
+            Transform::from_translation(position.extend(0.)),
+            ReadMassProperties(MassProperties {
+                mass: rng.gen_range(10.0..10_000.),
+                ..Default::default()
+            }),
+            ExternalForce::default(),
+        ));
+    }
+    app
+}
+
+fn gravity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gravity");
+    for bodies in BODY_COUNTS {
+        // a frame takes seconds at the top end without threads
+        group.sample_size(if bodies >= 1_000 { 10 } else { 100 });
+        for (name, add_systems) in CANDIDATES {
+            let mut app = gravity_app(bodies, add_systems);
+            group.bench_with_input(BenchmarkId::new(name, bodies), &bodies, |b, _| {
+                b.iter(|| app.update())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, gravity);
+criterion_main!(benches);
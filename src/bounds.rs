@@ -0,0 +1,331 @@
+use crate::{
+    apply_gravitational_forces,
+    camera::{camera_follow_system, CameraSettings, GameCamera},
+    components::{Asteroid, DespawnOutOfBounds, Lifetime, NoGhost},
+    orbit::Celestial,
+    BoundaryMode, WinSize, WorldBounds, DESPAWN_MARGIN, GHOST_MARGIN, SOFT_BOUNDARY_STIFFNESS,
+    WALL_THICKNESS,
+};
+use bevy::{
+    ecs::system::SystemParam, math::Vec3Swizzles, prelude::*, transform::TransformSystem,
+    utils::HashMap,
+};
+use bevy_rapier2d::prelude::{Collider, ExternalForce, ReadMassProperties, RigidBody, Sensor};
+
+pub struct BoundsPlugin;
+
+impl Plugin for BoundsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WorldBounds::default())
+            .add_system(boundary_mode_system)
+            .add_system(boundary_system.after(apply_gravitational_forces))
+            .add_system(despawn_out_of_bounds_system)
+            .add_system(lifetime_system)
+            // once the camera has settled, so ghosts appear wherever it can see
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                ghost_system
+                    .after(camera_follow_system)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// One of the fixed colliders around the world in `BoundaryMode::Walls`.
+#[derive(Component)]
+struct BoundaryWall;
+
+/// Puts up or takes down the walls and fits the camera to the world when the mode changes.
+fn boundary_mode_system(
+    mut commands: Commands,
+    world_bounds: Res<WorldBounds>,
+    mut camera_settings: ResMut<CameraSettings>,
+    wall_query: Query<Entity, With<BoundaryWall>>,
+) {
+    if !world_bounds.is_changed() {
+        return;
+    }
+
+    for entity in wall_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    if world_bounds.mode == BoundaryMode::Walls {
+        let half = world_bounds.size / 2.;
+        let thickness = WALL_THICKNESS / 2.;
+        // each wall as (centre, half extents), long enough to close the corners
+        let walls = [
+            (
+                Vec2::new(0., half.y + thickness),
+                Vec2::new(half.x + WALL_THICKNESS, thickness),
+            ),
+            (
+                Vec2::new(0., -half.y - thickness),
+                Vec2::new(half.x + WALL_THICKNESS, thickness),
+            ),
+            (
+                Vec2::new(half.x + thickness, 0.),
+                Vec2::new(thickness, half.y + WALL_THICKNESS),
+            ),
+            (
+                Vec2::new(-half.x - thickness, 0.),
+                Vec2::new(thickness, half.y + WALL_THICKNESS),
+            ),
+        ];
+        for (centre, half_extents) in walls {
+            commands
+                .spawn(RigidBody::Fixed)
+                .insert(Collider::cuboid(half_extents.x, half_extents.y))
+                .insert(TransformBundle::from(Transform::from_translation(
+                    centre.extend(0.),
+                )))
+                .insert(BoundaryWall);
+        }
+    }
+
+    // soft edges let ships drift past them and a wrapping world has no edge at all
+    camera_settings.bounds = match world_bounds.mode {
+        BoundaryMode::Walls => Some(world_bounds.rect()),
+        _ => None,
+    };
+}
+
+/// Wraps bodies round to the opposite edge, or pushes them back in with a force added
+/// on top of the gravity already written into `ExternalForce`. Celestial asteroids are
+/// left to `leapfrog_system`, which does the same for them.
+pub(crate) fn boundary_system(
+    world_bounds: Res<WorldBounds>,
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut ExternalForce,
+            &ReadMassProperties,
+            Option<&DespawnOutOfBounds>,
+        ),
+        Without<Celestial>,
+    >,
+) {
+    let rect = world_bounds.rect();
+
+    for (mut transform, mut ext_force, mass_props, despawns) in query.iter_mut() {
+        let position = transform.translation.xy();
+        match world_bounds.mode {
+            BoundaryMode::Wrap => {
+                // only touch the transform when it moves, rapier teleports changed bodies
+                let wrapped = world_bounds.wrap(position);
+                if wrapped != position {
+                    transform.translation.x = wrapped.x;
+                    transform.translation.y = wrapped.y;
+                }
+            }
+            // projectiles fly on out and are despawned
+            BoundaryMode::Soft if despawns.is_none() => {
+                let outside = position - position.clamp(rect.min, rect.max);
+                if outside != Vec2::ZERO {
+                    ext_force.force -= outside * SOFT_BOUNDARY_STIFFNESS * mass_props.0.mass;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn despawn_out_of_bounds_system(
+    mut commands: Commands,
+    world_bounds: Res<WorldBounds>,
+    query: Query<(Entity, &Transform), With<DespawnOutOfBounds>>,
+) {
+    // nothing leaves a wrapping world, lifetimes clear projectiles up instead
+    if world_bounds.mode == BoundaryMode::Wrap {
+        return;
+    }
+
+    let half = world_bounds.size / 2. + DESPAWN_MARGIN;
+    for (entity, tf) in query.iter() {
+        let position = tf.translation.xy();
+        if position.x.abs() > half.x || position.y.abs() > half.y {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn lifetime_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Lifetime)>,
+) {
+    for (entity, mut lifetime) in query.iter_mut() {
+        lifetime.0.tick(time.delta());
+        if lifetime.0.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// A copy of a sprite drawn one world width or height away, so something crossing the
+/// seam of a wrapping world shows on both sides instead of popping over. Only what is
+/// drawn is copied, asteroid ghosts are sensors, so bodies on either side of the seam
+/// do not collide.
+#[derive(Component)]
+struct Ghost {
+    of: Entity,
+    shift: Vec2,
+}
+
+/// Sprites that get ghosts, anything already a ghost or marked `NoGhost` is left alone.
+type GhostedSprites<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static Sprite,
+        &'static Handle<Image>,
+    ),
+    (Without<Ghost>, Without<NoGhost>),
+>;
+
+/// Asteroids have no sprite, so their ghosts copy the collider the debug view draws.
+type GhostedAsteroids<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static Transform, &'static Collider),
+    (With<Asteroid>, Without<Ghost>),
+>;
+
+/// Everything a ghost can copy.
+#[derive(SystemParam)]
+struct Ghosted<'w, 's> {
+    sprites: GhostedSprites<'w, 's>,
+    atlas_sprites: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Transform,
+            &'static TextureAtlasSprite,
+            &'static Handle<TextureAtlas>,
+        ),
+        Without<Ghost>,
+    >,
+    asteroids: GhostedAsteroids<'w, 's>,
+}
+
+type Ghosts<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Ghost,
+        &'static mut Transform,
+        Option<&'static mut Sprite>,
+        Option<&'static mut TextureAtlasSprite>,
+    ),
+    Without<GameCamera>,
+>;
+
+fn ghost_system(
+    mut commands: Commands,
+    world_bounds: Res<WorldBounds>,
+    win_size: Res<WinSize>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    ghosted: Ghosted,
+    mut ghost_query: Ghosts,
+) {
+    // shifts a ghost is wanted at, keyed by what it copies
+    let mut wanted: HashMap<(Entity, IVec2), Vec2> = HashMap::default();
+
+    if world_bounds.mode == BoundaryMode::Wrap {
+        if let Ok((camera_tf, projection)) = camera_query.get_single() {
+            let half_view =
+                Vec2::new(win_size.w, win_size.h) / 2. * projection.scale + GHOST_MARGIN;
+            let view_centre = camera_tf.translation.xy();
+            let positions = ghosted
+                .sprites
+                .iter()
+                .map(|(entity, tf, ..)| (entity, tf.translation.xy()))
+                .chain(
+                    ghosted
+                        .atlas_sprites
+                        .iter()
+                        .map(|(entity, tf, ..)| (entity, tf.translation.xy())),
+                )
+                .chain(
+                    ghosted
+                        .asteroids
+                        .iter()
+                        .map(|(entity, tf, _)| (entity, tf.translation.xy())),
+                );
+
+            for (entity, position) in positions {
+                for x in -1..=1 {
+                    for y in -1..=1 {
+                        let cell = IVec2::new(x, y);
+                        let shift = cell.as_vec2() * world_bounds.size;
+                        let offset = (position + shift - view_centre).abs();
+                        if cell != IVec2::ZERO && offset.x < half_view.x && offset.y < half_view.y {
+                            wanted.insert((entity, cell), shift);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // move the ghosts still wanted along with what they copy, drop the rest
+    for (ghost_entity, ghost, mut ghost_tf, ghost_sprite, ghost_atlas_sprite) in
+        ghost_query.iter_mut()
+    {
+        let cell = (ghost.shift / world_bounds.size).round().as_ivec2();
+        if wanted.remove(&(ghost.of, cell)).is_none() {
+            commands.entity(ghost_entity).despawn();
+            continue;
+        }
+
+        if let Ok((_, tf, sprite, _)) = ghosted.sprites.get(ghost.of) {
+            *ghost_tf = tf.with_translation(tf.translation + ghost.shift.extend(0.));
+            if let Some(mut ghost_sprite) = ghost_sprite {
+                *ghost_sprite = sprite.clone();
+            }
+        } else if let Ok((_, tf, atlas_sprite, _)) = ghosted.atlas_sprites.get(ghost.of) {
+            *ghost_tf = tf.with_translation(tf.translation + ghost.shift.extend(0.));
+            if let Some(mut ghost_atlas_sprite) = ghost_atlas_sprite {
+                *ghost_atlas_sprite = atlas_sprite.clone();
+            }
+        } else if let Ok((_, tf, _)) = ghosted.asteroids.get(ghost.of) {
+            *ghost_tf = tf.with_translation(tf.translation + ghost.shift.extend(0.));
+        }
+    }
+
+    for ((entity, _), shift) in wanted {
+        let ghost = Ghost { of: entity, shift };
+        if let Ok((_, tf, sprite, texture)) = ghosted.sprites.get(entity) {
+            commands
+                .spawn(SpriteBundle {
+                    sprite: sprite.clone(),
+                    texture: texture.clone(),
+                    transform: tf.with_translation(tf.translation + shift.extend(0.)),
+                    ..Default::default()
+                })
+                .insert(ghost);
+        } else if let Ok((_, tf, atlas_sprite, atlas)) = ghosted.atlas_sprites.get(entity) {
+            commands
+                .spawn(SpriteSheetBundle {
+                    sprite: atlas_sprite.clone(),
+                    texture_atlas: atlas.clone(),
+                    transform: tf.with_translation(tf.translation + shift.extend(0.)),
+                    ..Default::default()
+                })
+                .insert(ghost);
+        } else if let Ok((_, tf, collider)) = ghosted.asteroids.get(entity) {
+            // a sensor gets drawn without anything bumping into it
+            commands
+                .spawn(collider.clone())
+                .insert(Sensor)
+                .insert(TransformBundle::from(
+                    tf.with_translation(tf.translation + shift.extend(0.)),
+                ))
+                .insert(ghost);
+        }
+    }
+}
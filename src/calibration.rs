@@ -0,0 +1,338 @@
+use crate::{
+    controls::{raw_stick, Stick, StickConfig, StickSettings},
+    settings::settings_menu_navigation_system,
+};
+use bevy::prelude::*;
+
+pub struct CalibrationPlugin;
+
+impl Plugin for CalibrationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Calibration::default())
+            .add_startup_system(calibration_setup_system)
+            .add_system(calibration_navigation_system.before(settings_menu_navigation_system))
+            .add_system(calibration_display_system.after(calibration_navigation_system));
+    }
+}
+
+const PANEL_SIZE: f32 = 200.; // the square the stick positions are drawn in
+const DOT_SIZE: f32 = 12.;
+
+/// One line of the calibration screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CalibrationRow {
+    Stick,
+    RadialDeadzone,
+    AxialDeadzone,
+    Curve,
+    Sensitivity,
+    SetCentre,
+    Reset,
+}
+
+impl CalibrationRow {
+    const ALL: [CalibrationRow; 7] = [
+        CalibrationRow::Stick,
+        CalibrationRow::RadialDeadzone,
+        CalibrationRow::AxialDeadzone,
+        CalibrationRow::Curve,
+        CalibrationRow::Sensitivity,
+        CalibrationRow::SetCentre,
+        CalibrationRow::Reset,
+    ];
+}
+
+/// Opened from the settings menu, tunes the sticks while showing where they are.
+#[derive(Resource)]
+pub struct Calibration {
+    pub open: bool,
+    selected: usize,
+    stick: Stick,
+    /// the pad being shown, the last one to press a button
+    gamepad: Option<Gamepad>,
+    /// why the last change did not take, until the selection moves
+    notice: Option<String>,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            open: false,
+            selected: 0,
+            stick: Stick::Left,
+            gamepad: None,
+            notice: None,
+        }
+    }
+}
+
+#[derive(Component)]
+struct CalibrationText;
+
+#[derive(Component)]
+struct CalibrationPanel;
+
+/// raw position, where the stick physically is
+#[derive(Component)]
+struct RawDot;
+
+/// position after the deadzones, curve and sensitivity
+#[derive(Component)]
+struct ShapedDot;
+
+fn calibration_setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Black.ttf"),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(20.0),
+                    left: Val::Percent(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        )
+        .insert(Visibility::INVISIBLE)
+        .insert(CalibrationText);
+
+    let dot = |color: Color| NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            size: Size::new(Val::Px(DOT_SIZE), Val::Px(DOT_SIZE)),
+            ..Default::default()
+        },
+        background_color: color.into(),
+        ..Default::default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(20.0),
+                    right: Val::Percent(15.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(PANEL_SIZE), Val::Px(PANEL_SIZE)),
+                ..Default::default()
+            },
+            background_color: Color::rgba(1., 1., 1., 0.1).into(),
+            visibility: Visibility::INVISIBLE,
+            ..Default::default()
+        })
+        .insert(CalibrationPanel)
+        .with_children(|parent| {
+            parent.spawn(dot(Color::GRAY)).insert(RawDot);
+            parent.spawn(dot(Color::YELLOW)).insert(ShapedDot);
+        });
+}
+
+pub fn calibration_navigation_system(
+    kb: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    mut calibration: ResMut<Calibration>,
+    mut sticks: ResMut<StickConfig>,
+) {
+    if !calibration.open {
+        return;
+    }
+
+    // show whichever pad is being used, or any connected one
+    if let Some(button) = buttons.get_just_pressed().next() {
+        if calibration.gamepad != Some(button.gamepad) {
+            calibration.gamepad = Some(button.gamepad);
+        }
+    }
+    if calibration
+        .gamepad
+        .is_none_or(|gamepad| !gamepads.contains(gamepad))
+    {
+        let fallback = gamepads.iter().min_by_key(|gamepad| gamepad.id);
+        if calibration.gamepad != fallback {
+            calibration.gamepad = fallback;
+        }
+    }
+
+    let pad_pressed = |button_type| {
+        buttons
+            .get_just_pressed()
+            .any(|button| button.button_type == button_type)
+    };
+
+    // back to the settings menu, keeping the changes
+    if kb.just_pressed(KeyCode::Escape) || pad_pressed(GamepadButtonType::East) {
+        calibration.open = false;
+        sticks.save();
+        return;
+    }
+
+    let rows = CalibrationRow::ALL.len();
+    if kb.just_pressed(KeyCode::Down) || pad_pressed(GamepadButtonType::DPadDown) {
+        calibration.selected = (calibration.selected + 1) % rows;
+        calibration.notice = None;
+    }
+    if kb.just_pressed(KeyCode::Up) || pad_pressed(GamepadButtonType::DPadUp) {
+        calibration.selected = (calibration.selected + rows - 1) % rows;
+        calibration.notice = None;
+    }
+
+    let mut step = 0.;
+    if kb.just_pressed(KeyCode::Right) || pad_pressed(GamepadButtonType::DPadRight) {
+        step += 1.;
+    }
+    if kb.just_pressed(KeyCode::Left) || pad_pressed(GamepadButtonType::DPadLeft) {
+        step -= 1.;
+    }
+    let confirm = kb.just_pressed(KeyCode::Return) || pad_pressed(GamepadButtonType::South);
+
+    let stick = calibration.stick;
+    match CalibrationRow::ALL[calibration.selected] {
+        CalibrationRow::Stick if step != 0. || confirm => {
+            calibration.stick = match stick {
+                Stick::Left => Stick::Right,
+                Stick::Right => Stick::Left,
+            }
+        }
+        CalibrationRow::RadialDeadzone if step != 0. => {
+            let settings = sticks.get_mut(stick);
+            settings.radial_deadzone = (settings.radial_deadzone + step * 0.05).clamp(0., 0.9);
+        }
+        CalibrationRow::AxialDeadzone if step != 0. => {
+            let settings = sticks.get_mut(stick);
+            settings.axial_deadzone = (settings.axial_deadzone + step * 0.05).clamp(0., 0.5);
+        }
+        CalibrationRow::Curve if step != 0. || confirm => {
+            let settings = sticks.get_mut(stick);
+            settings.curve = settings.curve.next();
+        }
+        CalibrationRow::Sensitivity if step != 0. => {
+            let settings = sticks.get_mut(stick);
+            settings.sensitivity = (settings.sensitivity + step * 0.1).clamp(0.5, 2.);
+        }
+        CalibrationRow::SetCentre if confirm => {
+            if let Some(gamepad) = calibration.gamepad {
+                let rest = raw_stick(&axes, gamepad, stick);
+                // a stick pushed this far is being held, not drifting
+                calibration.notice = if rest.length() < 0.3 {
+                    sticks.get_mut(stick).centre = rest;
+                    None
+                } else {
+                    Some(format!(
+                        "Leave the {} at rest to set its centre",
+                        stick.label().to_lowercase()
+                    ))
+                };
+            }
+        }
+        CalibrationRow::Reset if confirm => *sticks.get_mut(stick) = StickSettings::default(),
+        _ => {}
+    }
+}
+
+fn calibration_display_system(
+    calibration: Res<Calibration>,
+    sticks: Res<StickConfig>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<CalibrationText>>,
+    mut panel_query: Query<&mut Visibility, (With<CalibrationPanel>, Without<CalibrationText>)>,
+    mut raw_dot_query: Query<&mut Style, (With<RawDot>, Without<ShapedDot>)>,
+    mut shaped_dot_query: Query<&mut Style, With<ShapedDot>>,
+) {
+    if calibration.is_changed() {
+        for mut visibility in panel_query.iter_mut() {
+            visibility.is_visible = calibration.open;
+        }
+        for (_, mut visibility) in text_query.iter_mut() {
+            visibility.is_visible = calibration.open;
+        }
+    }
+    if !calibration.open {
+        return;
+    }
+
+    let settings = sticks.get(calibration.stick);
+    let raw = calibration.gamepad.map_or(Vec2::ZERO, |gamepad| {
+        raw_stick(&axes, gamepad, calibration.stick)
+    });
+    let shaped = settings.apply(raw);
+
+    let mut lines = vec![
+        "CALIBRATE STICKS (Esc / B to go back)".to_string(),
+        String::new(),
+    ];
+    for (i, row) in CalibrationRow::ALL.iter().enumerate() {
+        let cursor = if i == calibration.selected {
+            "> "
+        } else {
+            "  "
+        };
+        let value = match row {
+            CalibrationRow::Stick => format!("Stick: < {} >", calibration.stick.label()),
+            CalibrationRow::RadialDeadzone => {
+                format!(
+                    "Radial deadzone: < {:.0}% >",
+                    settings.radial_deadzone * 100.
+                )
+            }
+            CalibrationRow::AxialDeadzone => {
+                format!("Axial deadzone: < {:.0}% >", settings.axial_deadzone * 100.)
+            }
+            CalibrationRow::Curve => format!("Response curve: < {} >", settings.curve.label()),
+            CalibrationRow::Sensitivity => {
+                format!("Sensitivity: < {:.1}x >", settings.sensitivity)
+            }
+            CalibrationRow::SetCentre => format!(
+                "Set centre, leave the stick at rest ({:.2}, {:.2})",
+                settings.centre.x, settings.centre.y
+            ),
+            CalibrationRow::Reset => "Reset this stick".to_string(),
+        };
+        lines.push(format!("{cursor}{value}"));
+    }
+    lines.push(String::new());
+    lines.push(match calibration.gamepad {
+        Some(gamepad) => format!(
+            "Gamepad {}: ({:.2}, {:.2}) -> ({:.2}, {:.2})",
+            gamepad.id, raw.x, raw.y, shaped.x, shaped.y
+        ),
+        None => "Connect a gamepad to calibrate it".to_string(),
+    });
+    if let Some(notice) = &calibration.notice {
+        lines.push(notice.clone());
+    }
+
+    let value = lines.join("\n");
+    for (mut text, _) in text_query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+
+    // -1. to 1. on both axes across the panel, up is up
+    let place = |style: &mut Style, position: Vec2| {
+        let left = (position.x + 1.) / 2. * PANEL_SIZE - DOT_SIZE / 2.;
+        let top = (1. - position.y) / 2. * PANEL_SIZE - DOT_SIZE / 2.;
+        if style.position.left != Val::Px(left) || style.position.top != Val::Px(top) {
+            style.position.left = Val::Px(left);
+            style.position.top = Val::Px(top);
+        }
+    };
+    for mut style in raw_dot_query.iter_mut() {
+        place(&mut style, raw.clamp(Vec2::NEG_ONE, Vec2::ONE));
+    }
+    for mut style in shaped_dot_query.iter_mut() {
+        place(&mut style, shaped);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{
+    components::{Asteroid, Explosion, GravitySource, Player},
+    controls::{Action, ActionState, PauseState},
+    BoundaryMode, WinSize, WorldBounds, CAMERA_FOLLOW_RATE, CAMERA_FRAME_MARGIN, CAMERA_LOOK_AHEAD,
+    CAMERA_MANUAL_ZOOM_RATE, CAMERA_MAX_LOOK_AHEAD, CAMERA_MAX_ZOOM, CAMERA_MIN_ZOOM,
+    CAMERA_NEARBY_RANGE, CAMERA_SCROLL_ZOOM, CAMERA_SPEED_ZOOM, CAMERA_ZOOM_RATE, SHAKE_DECAY,
+    SHAKE_EXPLOSION, SHAKE_EXPLOSION_RADIUS, SHAKE_MAX_OFFSET,
+};
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    math::Vec3Swizzles,
+    prelude::*,
+    transform::TransformSystem,
+};
+use bevy_rapier2d::prelude::Velocity;
+use rand::Rng;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CameraSettings::default())
+            .add_system(camera_zoom_input_system)
+            .add_system(camera_shake_system)
+            // after the physics has moved the ships, before the frame is drawn
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                camera_follow_system.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ZoomMode {
+    /// pulls back with speed and to keep every ship and nearby body in view
+    #[default]
+    Auto,
+    /// set with the zoom buttons or the mouse wheel
+    Manual,
+}
+
+impl ZoomMode {
+    pub fn next(&self) -> Self {
+        match self {
+            ZoomMode::Auto => ZoomMode::Manual,
+            ZoomMode::Manual => ZoomMode::Auto,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ZoomMode::Auto => "Auto",
+            ZoomMode::Manual => "Manual",
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct CameraSettings {
+    pub zoom_mode: ZoomMode,
+    /// projection scale used in manual mode, above 1. shows more of the world
+    pub manual_zoom: f32,
+    pub shake: bool,
+    /// the camera never shows past these, when set
+    pub bounds: Option<Rect>,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            zoom_mode: ZoomMode::Auto,
+            manual_zoom: 1.,
+            shake: true,
+            bounds: None,
+        }
+    }
+}
+
+/// The camera following the ships, added to the 2d camera in `setup_system`.
+#[derive(Component)]
+pub struct GameCamera {
+    /// the smoothed point being looked at, before shake
+    focus: Vec2,
+    zoom: f32,
+    /// how shaken the camera is, from 0. to 1.
+    trauma: f32,
+}
+
+impl Default for GameCamera {
+    fn default() -> Self {
+        Self {
+            focus: Vec2::ZERO,
+            zoom: 1.,
+            trauma: 0.,
+        }
+    }
+}
+
+/// Zoom buttons and the mouse wheel switch to manual zoom.
+fn camera_zoom_input_system(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    pause_state: Res<PauseState>,
+    mut scroll_evr: EventReader<MouseWheel>,
+    mut settings: ResMut<CameraSettings>,
+    camera_query: Query<&GameCamera>,
+) {
+    let mut factor = 1.;
+    for ev in scroll_evr.iter() {
+        let lines = match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / 20.,
+        };
+        // scrolling up zooms in
+        factor *= CAMERA_SCROLL_ZOOM.powf(-lines);
+    }
+    if pause_state.is_paused() {
+        return;
+    }
+
+    let held_rate = CAMERA_MANUAL_ZOOM_RATE.powf(time.delta_seconds());
+    if actions.any_pressed(Action::ZoomOut) {
+        factor *= held_rate;
+    }
+    if actions.any_pressed(Action::ZoomIn) {
+        factor /= held_rate;
+    }
+
+    if factor != 1. {
+        // carry on from wherever auto zoom had got to
+        if settings.zoom_mode == ZoomMode::Auto {
+            settings.zoom_mode = ZoomMode::Manual;
+            settings.manual_zoom = camera_query.get_single().map_or(1., |camera| camera.zoom);
+        }
+        settings.manual_zoom =
+            (settings.manual_zoom * factor).clamp(CAMERA_MIN_ZOOM, CAMERA_MAX_ZOOM);
+    }
+}
+
+/// Explosions shake the camera, harder the closer they are to what it is looking at.
+fn camera_shake_system(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    world_bounds: Res<WorldBounds>,
+    explosion_query: Query<&Transform, Added<Explosion>>,
+    mut camera_query: Query<&mut GameCamera>,
+) {
+    for mut camera in camera_query.iter_mut() {
+        if settings.shake {
+            for explosion_tf in explosion_query.iter() {
+                let distance = world_bounds
+                    .offset(camera.focus, explosion_tf.translation.xy())
+                    .length();
+                let falloff = (1. - distance / SHAKE_EXPLOSION_RADIUS).max(0.);
+                camera.trauma = (camera.trauma + SHAKE_EXPLOSION * falloff).min(1.);
+            }
+        } else {
+            camera.trauma = 0.;
+        }
+        camera.trauma = (camera.trauma - SHAKE_DECAY * time.delta_seconds()).max(0.);
+    }
+}
+
+/// Bodies auto zoom fits in when they are near a ship.
+type IsMassive = Or<(With<Asteroid>, With<GravitySource>)>;
+
+pub fn camera_follow_system(
+    time: Res<Time>,
+    win_size: Res<WinSize>,
+    world_bounds: Res<WorldBounds>,
+    settings: Res<CameraSettings>,
+    player_query: Query<(&Transform, &Velocity), With<Player>>,
+    body_query: Query<&Transform, (IsMassive, Without<GameCamera>)>,
+    mut camera_query: Query<
+        (&mut GameCamera, &mut Transform, &mut OrthographicProjection),
+        Without<Player>,
+    >,
+) {
+    let (mut camera, mut camera_tf, mut projection) = match camera_query.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let dt = time.delta_seconds();
+
+    // everything is placed relative to the focus, so in a wrapping world the camera
+    // follows ships across the seam instead of sweeping back over the whole world
+    let focus = camera.focus;
+    let relative = |position: Vec2| world_bounds.offset(focus, position);
+
+    // with every ship gone the camera stays where it was
+    let ships: Vec<(Vec2, Vec2)> = player_query
+        .iter()
+        .map(|(tf, velocity)| (relative(tf.translation.xy()), velocity.linvel))
+        .collect();
+    if !ships.is_empty() {
+        let count = ships.len() as f32;
+        let centre = ships.iter().map(|(position, _)| *position).sum::<Vec2>() / count;
+        let velocity = ships.iter().map(|(_, velocity)| *velocity).sum::<Vec2>() / count;
+
+        // lead the ships so there is more to see in the direction they are going
+        let target =
+            centre + (velocity * CAMERA_LOOK_AHEAD).clamp_length_max(CAMERA_MAX_LOOK_AHEAD);
+        let follow = 1. - (-CAMERA_FOLLOW_RATE * dt).exp();
+        camera.focus += target * follow;
+        if world_bounds.mode == BoundaryMode::Wrap {
+            camera.focus = world_bounds.wrap(camera.focus);
+        }
+
+        let target_zoom = match settings.zoom_mode {
+            ZoomMode::Manual => settings.manual_zoom,
+            ZoomMode::Auto => {
+                // everything worth seeing, the ships and any body close to one of them
+                let nearby_bodies = body_query
+                    .iter()
+                    .map(|tf| relative(tf.translation.xy()))
+                    .filter(|body| {
+                        ships
+                            .iter()
+                            .any(|(ship, _)| ship.distance(*body) < CAMERA_NEARBY_RANGE)
+                    });
+                // the focus has moved on by `target * follow` since the offsets were taken
+                let moved = target * follow;
+                let extent = ships
+                    .iter()
+                    .map(|(position, _)| *position)
+                    .chain(nearby_bodies)
+                    .map(|position| (position - moved).abs() + CAMERA_FRAME_MARGIN)
+                    .fold(Vec2::ZERO, Vec2::max);
+                let fit = (extent.x / (win_size.w / 2.)).max(extent.y / (win_size.h / 2.));
+                let speed = 1. + velocity.length() / CAMERA_SPEED_ZOOM;
+                fit.max(speed).clamp(1., CAMERA_MAX_ZOOM)
+            }
+        };
+        let zoom = 1. - (-CAMERA_ZOOM_RATE * dt).exp();
+        camera.zoom += (target_zoom - camera.zoom) * zoom;
+    }
+
+    // keep the view inside the bounds, centred on them when it is too big to fit
+    let mut position = camera.focus;
+    if let Some(bounds) = settings.bounds {
+        let half_view = Vec2::new(win_size.w, win_size.h) / 2. * camera.zoom;
+        let min = bounds.min + half_view;
+        let max = bounds.max - half_view;
+        let centre = bounds.center();
+        position.x = if min.x < max.x {
+            position.x.clamp(min.x, max.x)
+        } else {
+            centre.x
+        };
+        position.y = if min.y < max.y {
+            position.y.clamp(min.y, max.y)
+        } else {
+            centre.y
+        };
+    }
+
+    // trauma squared so small knocks barely register
+    let shake = camera.trauma.powi(2) * SHAKE_MAX_OFFSET * camera.zoom;
+    if shake > 0. {
+        let mut rng = rand::thread_rng();
+        position += Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)) * shake;
+    }
+
+    camera_tf.translation.x = position.x;
+    camera_tf.translation.y = position.y;
+    if projection.scale != camera.zoom {
+        projection.scale = camera.zoom;
+    }
+}
@@ -1,4 +1,4 @@
-use bevy::{ecs::component, prelude::Component, prelude::*};
+use bevy::{ecs::component, prelude::Component, prelude::*, utils::HashMap};
 
 // common components
 #[derive(Component)]
@@ -26,6 +26,26 @@ pub struct Movable {
 #[derive(Component)]
 pub struct Laser;
 
+/// Despawns the entity when the timer runs out.
+#[derive(Component)]
+pub struct Lifetime(pub Timer);
+
+impl Lifetime {
+    pub fn from_seconds(seconds: f32) -> Self {
+        Lifetime(Timer::from_seconds(seconds, TimerMode::Once))
+    }
+}
+
+/// Despawns short lived things like projectiles once they leave the world, everything
+/// else is kept inside by the world's `BoundaryMode`.
+#[derive(Component)]
+pub struct DespawnOutOfBounds;
+
+/// Kept off the far side of a wrapping world's seam, for sprites like the field overlay
+/// that already follow the view across it.
+#[derive(Component)]
+pub struct NoGhost;
+
 #[derive(Component)]
 pub struct SpriteSize(pub Vec2);
 
@@ -35,17 +55,132 @@ impl From<(f32, f32)> for SpriteSize {
     }
 }
 
+/// Point mass that pulls on bodies without being a rapier body itself.
+#[derive(Component)]
+pub struct GravitySource {
+    pub mass: f32,
+}
+
+/// The large bodies orbiting each other in the play field.
+#[derive(Component)]
+pub struct Asteroid;
+
+/// When an asteroid last scored for being shot, so it cannot be farmed for points.
+#[derive(Component, Default)]
+pub struct AsteroidHitScored(pub Option<f64>);
+
+/// Tracks close passes of massive bodies, for slingshot bonuses and curved shots.
+#[derive(Component, Default)]
+pub struct GravityAssist {
+    pub body: Option<Entity>,           // body currently being passed
+    pub periapsis: f32,                 // closest approach to that body during the pass
+    pub delta_v: f32,                   // speed gained from that body's pull during the pass
+    pub launch_direction: Option<Vec2>, // first direction of travel
+    pub bend: f32,                      // radians turned away from the launch direction
+}
+
 // player components
 #[derive(Component)]
 pub struct Player;
 
+/// Which local player a ship belongs to, counting from 0.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlayerId(pub usize);
+
+impl PlayerId {
+    pub fn label(&self) -> String {
+        format!("P{}", self.0 + 1)
+    }
+
+    /// tint for the ship, its lasers and its HUD panel
+    pub fn color(&self) -> Color {
+        match self.0 {
+            0 => Color::WHITE,
+            1 => Color::rgb(0.4, 0.8, 1.),
+            2 => Color::rgb(0.5, 1., 0.5),
+            _ => Color::rgb(1., 0.55, 0.8),
+        }
+    }
+}
+
+/// The player that fired a laser, missile or gravity well, for scoring and friendly fire.
+#[derive(Component)]
+pub struct FromPlayer(pub PlayerId);
+
+/// Weapon heat, builds per shot and forces a cooldown once it overheats.
+#[derive(Component)]
+pub struct WeaponHeat {
+    pub heat: f32, // 0. (cold) to 1. (overheated)
+    pub overheated: bool,
+    pub last_fired: f64, // -1 if not fired
+}
+
+impl Default for WeaponHeat {
+    fn default() -> Self {
+        Self {
+            heat: 0.,
+            overheated: false,
+            last_fired: -1.,
+        }
+    }
+}
+
+impl WeaponHeat {
+    /// true if the fire rate allows a shot at `time` and the weapon is not overheated
+    pub fn can_fire(&self, time: f64, fire_interval: f64) -> bool {
+        !self.overheated && (self.last_fired == -1. || time >= self.last_fired + fire_interval)
+    }
+
+    pub fn fired(&mut self, time: f64, heat_per_shot: f32) {
+        self.last_fired = time;
+        self.heat = (self.heat + heat_per_shot).min(1.);
+        if self.heat >= 1. {
+            self.overheated = true;
+        }
+    }
+
+    pub fn cool(&mut self, amount: f32, recover_below: f32) {
+        self.heat = (self.heat - amount).max(0.);
+        if self.overheated && self.heat <= recover_below {
+            self.overheated = false;
+        }
+    }
+}
+
+/// Cooldown between missile launches.
+#[derive(Component)]
+pub struct MissileLauncher {
+    pub last_fired: f64, // -1 if not fired
+}
+
+impl Default for MissileLauncher {
+    fn default() -> Self {
+        Self { last_fired: -1. }
+    }
+}
+
+// missile components
 #[derive(Component)]
-pub struct FromPlayer;
+pub struct Missile {
+    pub target: Option<Entity>,
+    pub fuel: Timer,
+}
+
+// gravity well components
+#[derive(Component)]
+pub struct GravityWell {
+    pub collapse: Timer,
+}
 
 // enemy components
 #[derive(Component)]
 pub struct Enemy;
 
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EnemyKind {
+    Invader,
+}
+
 #[derive(Component)]
 pub struct FromEnemy;
 
@@ -56,6 +191,13 @@ pub struct Explosion;
 #[derive(Component)]
 pub struct ExplosionToSpawn(pub Vec3);
 
+/// Added next to an `ExplosionToSpawn` to damage everything within the radius.
+#[derive(Component)]
+pub struct AreaDamage {
+    pub radius: f32,
+    pub owner: Option<PlayerId>, // credited with the kills
+}
+
 #[derive(Component)]
 pub struct ExplosionTimer(pub Timer);
 
@@ -64,3 +206,77 @@ impl Default for ExplosionTimer {
         Self(Timer::from_seconds(0.05, TimerMode::Repeating))
     }
 }
+
+// power-up components
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PowerUpKind {
+    Shield,
+    WeaponUpgrade,
+    ExtraLife,
+    ThrustBoost,
+    ScoreMultiplier,
+}
+
+impl PowerUpKind {
+    /// seconds the effect lasts, None if it is applied once on pickup
+    pub fn duration(&self) -> Option<f32> {
+        match self {
+            PowerUpKind::Shield => Some(crate::SHIELD_DURATION),
+            PowerUpKind::WeaponUpgrade => Some(crate::WEAPON_UPGRADE_DURATION),
+            PowerUpKind::ExtraLife => None,
+            PowerUpKind::ThrustBoost => Some(crate::THRUST_BOOST_DURATION),
+            PowerUpKind::ScoreMultiplier => Some(crate::SCORE_MULTIPLIER_DURATION),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::WeaponUpgrade => "Triple shot",
+            PowerUpKind::ExtraLife => "Extra life",
+            PowerUpKind::ThrustBoost => "Thrust boost",
+            PowerUpKind::ScoreMultiplier => "Score x2",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUpKind::Shield => Color::CYAN,
+            PowerUpKind::WeaponUpgrade => Color::ORANGE,
+            PowerUpKind::ExtraLife => Color::GREEN,
+            PowerUpKind::ThrustBoost => Color::YELLOW,
+            PowerUpKind::ScoreMultiplier => Color::GOLD,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    pub kind: PowerUpKind,
+    pub lifetime: Timer,
+}
+
+/// Timed power-up effects currently running on a player.
+#[derive(Component, Default)]
+pub struct ActivePowerUps {
+    pub timers: HashMap<PowerUpKind, Timer>,
+}
+
+impl ActivePowerUps {
+    /// starts the effect, or restarts it if it is already running
+    pub fn activate(&mut self, kind: PowerUpKind, duration: f32) {
+        self.timers
+            .insert(kind, Timer::from_seconds(duration, TimerMode::Once));
+    }
+
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.timers.contains_key(&kind)
+    }
+
+    pub fn tick(&mut self, delta: std::time::Duration) {
+        for timer in self.timers.values_mut() {
+            timer.tick(delta);
+        }
+        self.timers.retain(|_, timer| !timer.finished());
+    }
+}
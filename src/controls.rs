@@ -0,0 +1,876 @@
+use std::{collections::HashMap, fs, marker::PhantomData};
+
+use crate::{
+    components::PlayerId,
+    devices::{gamepad_join_system, DeviceManager},
+    telemetry::{SystemTimings, Timed},
+    CONTROLS_CONFIG, MAX_PLAYERS, STICKS_CONFIG,
+};
+use bevy::{
+    ecs::system::SystemParam,
+    input::{gamepad::GamepadSettings, InputSystem},
+    prelude::*,
+    utils::HashSet,
+};
+use bevy_rapier2d::prelude::RapierConfiguration;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load())
+            .insert_resource(StickConfig::load())
+            .insert_resource(ActionState::default())
+            .insert_resource(PauseState::default())
+            .insert_resource(ControlScheme::default())
+            .insert_resource(CursorWorld::default())
+            .add_startup_system(raw_axes_setup_system)
+            .add_startup_system(pause_overlay_setup_system)
+            .add_system_to_stage(CoreStage::PreUpdate, cursor_world_system.after(InputSystem))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                action_input_system
+                    .after(cursor_world_system)
+                    .after(gamepad_join_system),
+            )
+            .add_system(control_scheme_switch_system)
+            .add_system(pause_toggle_system)
+            .add_system(pause_apply_system.after(pause_toggle_system));
+    }
+}
+
+/// Everything the player can do, independent of the device doing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Thrust,
+    Strafe, // axis
+    Aim,    // axis
+    Fire,
+    Missile,
+    GravityWell,
+    Pause,
+    SwitchScheme,
+    ZoomIn,
+    ZoomOut,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::Thrust,
+        Action::Strafe,
+        Action::Aim,
+        Action::Fire,
+        Action::Missile,
+        Action::GravityWell,
+        Action::Pause,
+        Action::SwitchScheme,
+        Action::ZoomIn,
+        Action::ZoomOut,
+    ];
+
+    /// axis actions read a direction rather than a button
+    pub fn is_axis(&self) -> bool {
+        matches!(self, Action::Strafe | Action::Aim)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Thrust => "Thrust",
+            Action::Strafe => "Strafe",
+            Action::Aim => "Aim",
+            Action::Fire => "Fire",
+            Action::Missile => "Missile",
+            Action::GravityWell => "Gravity well",
+            Action::Pause => "Pause",
+            Action::SwitchScheme => "Switch controls",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    /// four keys read as a direction
+    KeyAxis {
+        up: KeyCode,
+        down: KeyCode,
+        left: KeyCode,
+        right: KeyCode,
+    },
+    LeftStick,
+    RightStick,
+    /// from the ship towards the mouse cursor
+    Cursor,
+}
+
+pub const WASD: Binding = Binding::KeyAxis {
+    up: KeyCode::W,
+    down: KeyCode::S,
+    left: KeyCode::A,
+    right: KeyCode::D,
+};
+
+pub const ARROWS: Binding = Binding::KeyAxis {
+    up: KeyCode::Up,
+    down: KeyCode::Down,
+    left: KeyCode::Left,
+    right: KeyCode::Right,
+};
+
+impl Binding {
+    pub fn is_gamepad(&self) -> bool {
+        matches!(
+            self,
+            Binding::GamepadButton(_) | Binding::LeftStick | Binding::RightStick
+        )
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{key:?}"),
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::GamepadButton(button) => format!("{button:?}"),
+            binding if *binding == WASD => "WASD".to_string(),
+            binding if *binding == ARROWS => "Arrows".to_string(),
+            Binding::KeyAxis {
+                up,
+                down,
+                left,
+                right,
+            } => format!("{up:?}/{left:?}/{down:?}/{right:?}"),
+            Binding::LeftStick => "Left stick".to_string(),
+            Binding::RightStick => "Right stick".to_string(),
+            Binding::Cursor => "Mouse cursor".to_string(),
+        }
+    }
+}
+
+/// Which bindings drive each action, saved to `CONTROLS_CONFIG`.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct InputBindings(pub HashMap<Action, Vec<Binding>>);
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self(HashMap::from([
+            (
+                Action::Thrust,
+                vec![
+                    Binding::Key(KeyCode::LShift),
+                    Binding::GamepadButton(GamepadButtonType::LeftTrigger2),
+                ],
+            ),
+            (Action::Strafe, vec![WASD, Binding::LeftStick]),
+            (Action::Aim, vec![ARROWS, Binding::RightStick]),
+            (
+                Action::Fire,
+                vec![
+                    Binding::Key(KeyCode::Space),
+                    Binding::GamepadButton(GamepadButtonType::RightTrigger2),
+                ],
+            ),
+            (
+                Action::Missile,
+                vec![
+                    Binding::Key(KeyCode::E),
+                    Binding::GamepadButton(GamepadButtonType::RightTrigger),
+                ],
+            ),
+            (
+                Action::GravityWell,
+                vec![
+                    Binding::Key(KeyCode::Q),
+                    Binding::GamepadButton(GamepadButtonType::West),
+                ],
+            ),
+            (
+                Action::Pause,
+                vec![
+                    Binding::Key(KeyCode::Escape),
+                    Binding::GamepadButton(GamepadButtonType::Start),
+                ],
+            ),
+            (
+                Action::SwitchScheme,
+                vec![
+                    Binding::Key(KeyCode::Tab),
+                    Binding::GamepadButton(GamepadButtonType::Select),
+                ],
+            ),
+            (
+                Action::ZoomIn,
+                vec![
+                    Binding::Key(KeyCode::Equals),
+                    Binding::GamepadButton(GamepadButtonType::DPadUp),
+                ],
+            ),
+            (
+                Action::ZoomOut,
+                vec![
+                    Binding::Key(KeyCode::Minus),
+                    Binding::GamepadButton(GamepadButtonType::DPadDown),
+                ],
+            ),
+        ]))
+    }
+}
+
+impl InputBindings {
+    /// reads the config file, falling back to the defaults if it is missing or invalid
+    pub fn load() -> Self {
+        match load_config::<Self>(CONTROLS_CONFIG) {
+            Some(mut bindings) => {
+                // actions added since the file was written keep their defaults
+                for (action, defaults) in Self::default().0 {
+                    bindings.0.entry(action).or_insert(defaults);
+                }
+                bindings
+            }
+            None => Self::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        save_config(CONTROLS_CONFIG, self);
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// swaps out the bindings on the same kind of device as `binding`, keeping the others
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        bindings.retain(|existing| existing.is_gamepad() != binding.is_gamepad());
+        bindings.push(binding);
+    }
+}
+
+fn load_config<T: DeserializeOwned>(path: &str) -> Option<T> {
+    let contents = fs::read_to_string(path).ok()?;
+    match ron::from_str(&contents) {
+        Ok(config) => Some(config),
+        Err(err) => {
+            warn!("invalid {path}, using the defaults: {err}");
+            None
+        }
+    }
+}
+
+fn save_config<T: Serialize>(path: &str, config: &T) {
+    let contents = match ron::ser::to_string_pretty(config, ron::ser::PrettyConfig::default()) {
+        Ok(contents) => contents,
+        Err(err) => {
+            warn!("could not serialise {path}: {err}");
+            return;
+        }
+    };
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Err(err) = fs::write(path, contents) {
+        warn!("could not save {path}: {err}");
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+impl Stick {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Stick::Left => "Left stick",
+            Stick::Right => "Right stick",
+        }
+    }
+
+    pub fn axes(&self) -> (GamepadAxisType, GamepadAxisType) {
+        match self {
+            Stick::Left => (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+            Stick::Right => (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+        }
+    }
+}
+
+/// How far a stick is pushed maps to how hard the action is held.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    /// finer control near the centre
+    Quadratic,
+    Cubic,
+}
+
+impl ResponseCurve {
+    pub fn next(&self) -> Self {
+        match self {
+            ResponseCurve::Linear => ResponseCurve::Quadratic,
+            ResponseCurve::Quadratic => ResponseCurve::Cubic,
+            ResponseCurve::Cubic => ResponseCurve::Linear,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResponseCurve::Linear => "Linear",
+            ResponseCurve::Quadratic => "Quadratic",
+            ResponseCurve::Cubic => "Cubic",
+        }
+    }
+
+    fn apply(&self, magnitude: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => magnitude,
+            ResponseCurve::Quadratic => magnitude.powi(2),
+            ResponseCurve::Cubic => magnitude.powi(3),
+        }
+    }
+}
+
+/// Shapes the raw position of one stick, applied to every gamepad.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StickSettings {
+    /// pushes shorter than this read as centred
+    pub radial_deadzone: f32,
+    /// each axis below this reads as zero, so straight pushes stay straight
+    pub axial_deadzone: f32,
+    pub curve: ResponseCurve,
+    /// scales the push after the curve, above 1. reaches full strength early
+    pub sensitivity: f32,
+    /// where the stick rests, measured on the calibration screen
+    pub centre: Vec2,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        Self {
+            radial_deadzone: 0.15,
+            axial_deadzone: 0.05,
+            curve: ResponseCurve::Linear,
+            sensitivity: 1.,
+            centre: Vec2::ZERO,
+        }
+    }
+}
+
+impl StickSettings {
+    /// raw stick position -> direction with a length from 0. to 1.
+    pub fn apply(&self, raw: Vec2) -> Vec2 {
+        // the remaining range past a deadzone is stretched back to 0. to 1.
+        let rescale = |value: f32, deadzone: f32| {
+            if value <= deadzone {
+                0.
+            } else {
+                ((value - deadzone) / (1. - deadzone)).min(1.)
+            }
+        };
+
+        let centred = raw - self.centre;
+        let stick = Vec2::new(
+            rescale(centred.x.abs(), self.axial_deadzone) * centred.x.signum(),
+            rescale(centred.y.abs(), self.axial_deadzone) * centred.y.signum(),
+        );
+
+        let length = stick.length();
+        let magnitude = rescale(length, self.radial_deadzone);
+        if magnitude == 0. {
+            return Vec2::ZERO;
+        }
+        stick / length * (self.curve.apply(magnitude) * self.sensitivity).min(1.)
+    }
+}
+
+/// Settings for both sticks, saved to `STICKS_CONFIG`.
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StickConfig {
+    pub left: StickSettings,
+    pub right: StickSettings,
+}
+
+impl StickConfig {
+    pub fn load() -> Self {
+        load_config(STICKS_CONFIG).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        save_config(STICKS_CONFIG, self);
+    }
+
+    pub fn get(&self, stick: Stick) -> &StickSettings {
+        match stick {
+            Stick::Left => &self.left,
+            Stick::Right => &self.right,
+        }
+    }
+
+    pub fn get_mut(&mut self, stick: Stick) -> &mut StickSettings {
+        match stick {
+            Stick::Left => &mut self.left,
+            Stick::Right => &mut self.right,
+        }
+    }
+}
+
+/// Which devices drive the keyboard player's ship, switched at runtime with
+/// `Action::SwitchScheme`. The mouse goes with the keyboard, so gamepad players always
+/// play `Standard`.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ControlScheme {
+    #[default]
+    Standard,
+    /// twin-stick style, the ship faces the cursor, left click fires and right click thrusts
+    Mouse,
+}
+
+impl ControlScheme {
+    pub fn next(&self) -> Self {
+        match self {
+            ControlScheme::Standard => ControlScheme::Mouse,
+            ControlScheme::Mouse => ControlScheme::Standard,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ControlScheme::Standard => "Keyboard / gamepad",
+            ControlScheme::Mouse => "Mouse",
+        }
+    }
+
+    /// the configured bindings for `action` with this scheme's own bindings layered on top
+    fn bindings(&self, action: Action, configured: &[Binding]) -> Vec<Binding> {
+        match (self, action) {
+            (ControlScheme::Mouse, Action::Aim) => vec![Binding::Cursor],
+            (ControlScheme::Mouse, Action::Fire) => {
+                [configured, &[Binding::Mouse(MouseButton::Left)]].concat()
+            }
+            (ControlScheme::Mouse, Action::Thrust) => {
+                [configured, &[Binding::Mouse(MouseButton::Right)]].concat()
+            }
+            _ => configured.to_vec(),
+        }
+    }
+}
+
+/// Where the mouse cursor is in the world, `None` while it is outside the window.
+#[derive(Resource, Default)]
+pub struct CursorWorld(pub Option<Vec2>);
+
+/// The actions one player is holding this frame.
+#[derive(Default)]
+pub struct PlayerActions {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    axes: HashMap<Action, Vec2>,
+}
+
+impl PlayerActions {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// direction of an axis action, zero when it is not held
+    pub fn axis(&self, action: Action) -> Vec2 {
+        self.axes.get(&action).copied().unwrap_or(Vec2::ZERO)
+    }
+}
+
+/// The actions held this frame by every player, read by gameplay systems instead of
+/// the raw devices.
+#[derive(Resource, Default)]
+pub struct ActionState {
+    players: [PlayerActions; MAX_PLAYERS],
+}
+
+impl ActionState {
+    pub fn player(&self, id: PlayerId) -> &PlayerActions {
+        &self.players[id.0]
+    }
+
+    /// for actions that affect the whole game, like the shared camera
+    pub fn any_pressed(&self, action: Action) -> bool {
+        self.players.iter().any(|actions| actions.pressed(action))
+    }
+
+    /// for actions that affect the whole game, like pausing
+    pub fn any_just_pressed(&self, action: Action) -> bool {
+        self.players
+            .iter()
+            .any(|actions| actions.just_pressed(action))
+    }
+}
+
+/// Reasons the game can be paused, it runs again once none of them hold.
+#[derive(Resource, Default)]
+pub struct PauseState {
+    pub by_player: bool,
+    pub in_menu: bool,
+    pub waiting_for_device: bool, // a player's gamepad dropped out
+    pub by_debugger: bool,        // frozen from the debug inspector
+}
+
+impl PauseState {
+    pub fn is_paused(&self) -> bool {
+        self.by_player || self.in_menu || self.waiting_for_device || self.by_debugger
+    }
+}
+
+#[derive(Component)]
+pub struct PauseOverlay;
+
+/// The devices one player reads their bindings from.
+struct PlayerInput<'a> {
+    kb: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    buttons: &'a Input<GamepadButton>,
+    axes: &'a Axis<GamepadAxis>,
+    sticks: &'a StickConfig,
+    gamepad: Option<Gamepad>,
+    cursor_aim: Vec2,
+}
+
+impl PlayerInput<'_> {
+    fn read(&self, binding: &Binding) -> Vec2 {
+        let key_axis = |up, down, left, right| {
+            let pressed = |key| if self.kb.pressed(key) { 1. } else { 0. };
+            Vec2::new(pressed(right) - pressed(left), pressed(up) - pressed(down))
+        };
+        let stick = |stick: Stick| match self.gamepad {
+            Some(gamepad) => self
+                .sticks
+                .get(stick)
+                .apply(raw_stick(self.axes, gamepad, stick)),
+            None => Vec2::ZERO,
+        };
+        let held = |pressed: bool| if pressed { Vec2::X } else { Vec2::ZERO };
+
+        match *binding {
+            Binding::Key(key) => held(self.kb.pressed(key)),
+            Binding::Mouse(button) => held(self.mouse.pressed(button)),
+            Binding::GamepadButton(button_type) => held(self.gamepad.is_some_and(|gamepad| {
+                self.buttons
+                    .pressed(GamepadButton::new(gamepad, button_type))
+            })),
+            Binding::KeyAxis {
+                up,
+                down,
+                left,
+                right,
+            } => key_axis(up, down, left, right),
+            Binding::LeftStick => stick(Stick::Left),
+            Binding::RightStick => stick(Stick::Right),
+            Binding::Cursor => self.cursor_aim,
+        }
+    }
+}
+
+/// Where a stick sits before any of our deadzones or curves.
+pub fn raw_stick(axes: &Axis<GamepadAxis>, gamepad: Gamepad, stick: Stick) -> Vec2 {
+    let (x_axis, y_axis) = stick.axes();
+    Vec2::new(
+        axes.get(GamepadAxis::new(gamepad, x_axis)).unwrap_or(0.),
+        axes.get(GamepadAxis::new(gamepad, y_axis)).unwrap_or(0.),
+    )
+}
+
+/// Bevy clips each axis on its own by default, which squares off the stick and hides
+/// the resting offset from calibration. `StickSettings` does the filtering instead.
+fn raw_axes_setup_system(mut gamepad_settings: ResMut<GamepadSettings>) {
+    let axis_settings = &mut gamepad_settings.default_axis_settings;
+    axis_settings.set_livezone_upperbound(1.);
+    axis_settings.set_livezone_lowerbound(-1.);
+    axis_settings.set_deadzone_upperbound(0.);
+    axis_settings.set_deadzone_lowerbound(0.);
+    axis_settings.set_threshold(0.001);
+}
+
+/// Converts the cursor from window coordinates into world space through the camera.
+fn cursor_world_system(
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut cursor_world: ResMut<CursorWorld>,
+) {
+    cursor_world.0 = None;
+    let (camera, camera_tf) = match camera_query.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    if let Some(cursor) = window.cursor_position() {
+        // window position -> normalised device coordinates -> world
+        let window_size = Vec2::new(window.width(), window.height());
+        let ndc = cursor / window_size * 2. - Vec2::ONE;
+        let ndc_to_world = camera_tf.compute_matrix() * camera.projection_matrix().inverse();
+        cursor_world.0 = Some(ndc_to_world.project_point3(ndc.extend(-1.)).truncate());
+    }
+}
+
+/// Every device, before it is read for actions.
+#[derive(SystemParam)]
+struct RawInput<'w, 's> {
+    kb: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    cursor_world: Res<'w, CursorWorld>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// How the devices map onto actions.
+#[derive(SystemParam)]
+struct InputConfig<'w, 's> {
+    bindings: Res<'w, InputBindings>,
+    sticks: Res<'w, StickConfig>,
+    scheme: Res<'w, ControlScheme>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// Turns the raw devices into `ActionState` before any gameplay system runs.
+fn action_input_system(
+    input: RawInput,
+    config: InputConfig,
+    device_manager: Res<DeviceManager>,
+    player_query: Query<(&PlayerId, &Transform)>,
+    pause_state: Res<PauseState>,
+    mut actions: ResMut<ActionState>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Input);
+    let no_keys = Input::<KeyCode>::default();
+    let no_mouse = Input::<MouseButton>::default();
+
+    for (i, player_actions) in actions.players.iter_mut().enumerate() {
+        let id = PlayerId(i);
+        let previously_pressed = std::mem::take(&mut player_actions.pressed);
+        player_actions.axes.clear();
+
+        let devices = match device_manager.players.get(&id) {
+            Some(devices) => devices,
+            None => {
+                player_actions.just_pressed.clear();
+                continue;
+            }
+        };
+        // players without the keyboard read it as if nothing were held
+        let (kb, mouse) = if devices.keyboard {
+            (&*input.kb, &*input.mouse)
+        } else {
+            (&no_keys, &no_mouse)
+        };
+        let cursor_aim = match (input.cursor_world.0, devices.keyboard) {
+            (Some(cursor), true) => player_query
+                .iter()
+                .find(|(player_id, _)| **player_id == id)
+                .map_or(Vec2::ZERO, |(_, player_tf)| {
+                    (cursor - player_tf.translation.truncate()).normalize_or_zero()
+                }),
+            _ => Vec2::ZERO,
+        };
+        let scheme = if devices.keyboard {
+            *config.scheme
+        } else {
+            ControlScheme::Standard
+        };
+        let player_input = PlayerInput {
+            kb,
+            mouse,
+            buttons: &input.buttons,
+            axes: &input.axes,
+            sticks: &config.sticks,
+            gamepad: devices.gamepad,
+            cursor_aim,
+        };
+
+        for action in Action::ALL {
+            // only pause works while the game is paused
+            if pause_state.is_paused() && action != Action::Pause {
+                continue;
+            }
+
+            // the strongest binding wins when several are held at once
+            let value = scheme
+                .bindings(action, config.bindings.get(action))
+                .iter()
+                .map(|binding| player_input.read(binding))
+                .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+                .unwrap_or(Vec2::ZERO);
+
+            if action.is_axis() {
+                if value != Vec2::ZERO {
+                    player_actions.axes.insert(action, value);
+                }
+            } else if value != Vec2::ZERO {
+                player_actions.pressed.insert(action);
+            }
+        }
+
+        player_actions.just_pressed = player_actions
+            .pressed
+            .difference(&previously_pressed)
+            .copied()
+            .collect();
+    }
+}
+
+/// Only the keyboard player can switch, the scheme is theirs alone.
+fn control_scheme_switch_system(
+    actions: Res<ActionState>,
+    device_manager: Res<DeviceManager>,
+    mut scheme: ResMut<ControlScheme>,
+) {
+    let switched = device_manager.players.iter().any(|(id, devices)| {
+        devices.keyboard && actions.player(*id).just_pressed(Action::SwitchScheme)
+    });
+    if switched {
+        *scheme = scheme.next();
+        info!("control scheme: {}", scheme.label());
+    }
+}
+
+fn pause_overlay_setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "PAUSED",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Black.ttf"),
+                    font_size: 80.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(40.0),
+                    left: Val::Percent(38.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        )
+        .insert(Visibility::INVISIBLE)
+        .insert(PauseOverlay);
+}
+
+fn pause_toggle_system(actions: Res<ActionState>, mut pause_state: ResMut<PauseState>) {
+    if actions.any_just_pressed(Action::Pause) && !pause_state.in_menu {
+        pause_state.by_player = !pause_state.by_player;
+    }
+}
+
+/// Stops the clock and the physics pipeline while paused.
+pub fn pause_apply_system(
+    pause_state: Res<PauseState>,
+    mut time: ResMut<Time>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut overlay_query: Query<&mut Visibility, With<PauseOverlay>>,
+) {
+    if !pause_state.is_changed() {
+        return;
+    }
+
+    let paused = pause_state.is_paused();
+    if paused {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+    rapier_config.physics_pipeline_active = !paused;
+
+    for mut visibility in overlay_query.iter_mut() {
+        // the menu and the device overlay explain their own pauses
+        visibility.is_visible =
+            pause_state.by_player && !pause_state.in_menu && !pause_state.waiting_for_device;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(radial_deadzone: f32, axial_deadzone: f32) -> StickSettings {
+        StickSettings {
+            radial_deadzone,
+            axial_deadzone,
+            ..Default::default()
+        }
+    }
+
+    fn assert_near(actual: Vec2, expected: Vec2) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-5),
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn pushes_inside_the_radial_deadzone_read_as_centred() {
+        let stick = settings(0.2, 0.);
+        assert_eq!(stick.apply(Vec2::new(0.1, 0.1)), Vec2::ZERO);
+        assert_eq!(stick.apply(Vec2::new(0., -0.2)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn the_range_past_the_radial_deadzone_is_stretched_to_full() {
+        let stick = settings(0.2, 0.);
+        // just past the deadzone starts from nothing rather than jumping to 0.2
+        assert_near(stick.apply(Vec2::new(0.6, 0.)), Vec2::new(0.5, 0.));
+        assert_near(stick.apply(Vec2::new(-1., 0.)), Vec2::new(-1., 0.));
+        // the direction is kept, only the length is rescaled
+        assert_near(stick.apply(Vec2::new(0.36, 0.48)), Vec2::new(0.3, 0.4));
+    }
+
+    #[test]
+    fn the_axial_deadzone_keeps_straight_pushes_straight() {
+        let stick = settings(0., 0.1);
+        let pushed = stick.apply(Vec2::new(0.8, 0.05));
+        assert_eq!(pushed.y, 0.);
+        assert_near(pushed, Vec2::new(0.7 / 0.9, 0.));
+    }
+
+    #[test]
+    fn the_measured_centre_is_taken_off_first() {
+        let stick = StickSettings {
+            centre: Vec2::new(0.1, -0.05),
+            ..settings(0.2, 0.)
+        };
+        assert_eq!(stick.apply(Vec2::new(0.1, -0.05)), Vec2::ZERO);
+        assert_near(stick.apply(Vec2::new(0.7, -0.05)), Vec2::new(0.5, 0.));
+    }
+
+    #[test]
+    fn the_curve_and_sensitivity_apply_after_the_deadzone() {
+        let quadratic = StickSettings {
+            curve: ResponseCurve::Quadratic,
+            ..settings(0.2, 0.)
+        };
+        assert_near(quadratic.apply(Vec2::new(0.6, 0.)), Vec2::new(0.25, 0.));
+
+        let sensitive = StickSettings {
+            sensitivity: 2.,
+            ..settings(0.2, 0.)
+        };
+        assert_near(sensitive.apply(Vec2::new(0., 0.4)), Vec2::new(0., 0.5));
+        assert_near(sensitive.apply(Vec2::new(0., 0.9)), Vec2::new(0., 1.));
+    }
+}
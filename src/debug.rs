@@ -0,0 +1,305 @@
+use crate::{
+    components::{Asteroid, Enemy, Explosion, GravityWell, Laser, Missile, Pickup, Player},
+    controls::{pause_apply_system, CursorWorld, PauseState},
+    orbit::{OrbitIntegrator, OrbitStats},
+    telemetry::Timed,
+    BoundaryMode, WorldBounds,
+};
+use bevy::{
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    ecs::system::SystemParam,
+    prelude::*,
+};
+use bevy_rapier2d::prelude::{
+    ExternalForce, QueryFilter, RapierConfiguration, RapierContext, ReadMassProperties, Velocity,
+};
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DebugInspector::default())
+            .add_startup_system(debug_setup_system)
+            .add_system(debug_input_system)
+            .add_system(
+                debug_step_system
+                    .after(pause_apply_system)
+                    .after(debug_input_system),
+            )
+            .add_system(debug_text_system.after(debug_input_system));
+    }
+}
+
+/// How far a single step has got, each stage lasting one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Step {
+    #[default]
+    Idle,
+    /// the clock was started last frame, so this one has a real delta to step with
+    Starting,
+    /// the physics stepped last frame and is stopped again
+    Running,
+}
+
+/// F3 opens the inspector. While it is open F5 pauses, F6 steps a single frame and
+/// clicking a body selects it.
+#[derive(Resource, Default)]
+pub struct DebugInspector {
+    pub open: bool,
+    selected: Option<Entity>,
+    step: Step,
+}
+
+#[derive(Component)]
+struct DebugText;
+
+fn debug_setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Black.ttf"),
+                    font_size: 18.0,
+                    color: Color::rgb(0.6, 1., 0.6),
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        )
+        .insert(Visibility::INVISIBLE)
+        .insert(DebugText);
+}
+
+fn debug_input_system(
+    kb: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    cursor_world: Res<CursorWorld>,
+    world_bounds: Res<WorldBounds>,
+    rapier_context: Res<RapierContext>,
+    mut inspector: ResMut<DebugInspector>,
+    mut pause_state: ResMut<PauseState>,
+) {
+    if kb.just_pressed(KeyCode::F3) {
+        inspector.open = !inspector.open;
+        // closing the inspector lets go of its pause, or nothing would show it is held
+        if !inspector.open && pause_state.by_debugger {
+            pause_state.by_debugger = false;
+        }
+    }
+    if !inspector.open {
+        return;
+    }
+
+    if kb.just_pressed(KeyCode::F5) {
+        pause_state.by_debugger = !pause_state.by_debugger;
+    }
+    if kb.just_pressed(KeyCode::F6) && pause_state.by_debugger && inspector.step == Step::Idle {
+        inspector.step = Step::Starting;
+    }
+
+    if let (true, Some(cursor)) = (mouse.just_pressed(MouseButton::Left), cursor_world.0) {
+        // a click on a ghost picks the body it copies
+        let point = match world_bounds.mode {
+            BoundaryMode::Wrap => world_bounds.wrap(cursor),
+            _ => cursor,
+        };
+        let mut hit = None;
+        rapier_context.intersections_with_point(point, QueryFilter::default(), |entity| {
+            hit = Some(entity);
+            false
+        });
+        inspector.selected = hit;
+    }
+}
+
+/// Runs the clock and the physics for exactly one frame while paused from the inspector.
+/// The clock only takes a pause or unpause into account at the start of the next frame,
+/// so the step is spread over three.
+fn debug_step_system(
+    mut inspector: ResMut<DebugInspector>,
+    pause_state: Res<PauseState>,
+    mut time: ResMut<Time>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    match inspector.step {
+        Step::Idle => {}
+        Step::Starting if time.is_paused() => time.unpause(),
+        Step::Starting => {
+            // unpausing part way through leaves the physics to `pause_apply_system`
+            if pause_state.is_paused() {
+                rapier_config.physics_pipeline_active = true;
+                time.pause();
+            }
+            inspector.step = Step::Running;
+        }
+        Step::Running => {
+            if pause_state.is_paused() {
+                rapier_config.physics_pipeline_active = false;
+            }
+            inspector.step = Step::Idle;
+        }
+    }
+}
+
+type BodyQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Transform,
+        &'static ReadMassProperties,
+        Option<&'static Velocity>,
+        Option<&'static ExternalForce>,
+    ),
+>;
+
+/// What the inspector counts, and the bodies it can show the details of.
+#[derive(SystemParam)]
+struct Inspected<'w, 's> {
+    entity_query: Query<'w, 's, Entity>,
+    ships: Query<'w, 's, (), With<Player>>,
+    enemies: Query<'w, 's, (), With<Enemy>>,
+    asteroids: Query<'w, 's, (), With<Asteroid>>,
+    wells: Query<'w, 's, (), With<GravityWell>>,
+    lasers: Query<'w, 's, (), With<Laser>>,
+    missiles: Query<'w, 's, (), With<Missile>>,
+    pickups: Query<'w, 's, (), With<Pickup>>,
+    explosions: Query<'w, 's, (), With<Explosion>>,
+    body_query: BodyQuery<'w, 's>,
+}
+
+fn debug_text_system(
+    inspector: Res<DebugInspector>,
+    diagnostics: Res<Diagnostics>,
+    pause_state: Res<PauseState>,
+    orbit_integrator: Res<OrbitIntegrator>,
+    orbit_stats: Res<OrbitStats>,
+    inspected: Inspected,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<DebugText>>,
+) {
+    if inspector.is_changed() {
+        for (_, mut visibility) in text_query.iter_mut() {
+            visibility.is_visible = inspector.open;
+        }
+    }
+    if !inspector.open {
+        return;
+    }
+
+    let smoothed = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or(0.)
+    };
+
+    let mut lines = vec![
+        "DEBUG (F3 to close)".to_string(),
+        format!("FPS: {:.0}", smoothed(FrameTimeDiagnosticsPlugin::FPS)),
+    ];
+    // the gravity solver, rapier's step and the rest
+    lines.push("Time per frame:".to_string());
+    for timed in Timed::ALL {
+        lines.push(format!(
+            "  {} {:.2} ms",
+            timed.label(),
+            smoothed(timed.diagnostic_id())
+        ));
+    }
+    lines.extend([
+        format!("Entities: {}", inspected.entity_query.iter().count()),
+        format!(
+            "  ships {}, enemies {}, asteroids {}, wells {}",
+            inspected.ships.iter().count(),
+            inspected.enemies.iter().count(),
+            inspected.asteroids.iter().count(),
+            inspected.wells.iter().count()
+        ),
+        format!(
+            "  lasers {}, missiles {}, pickups {}, explosions {}",
+            inspected.lasers.iter().count(),
+            inspected.missiles.iter().count(),
+            inspected.pickups.iter().count(),
+            inspected.explosions.iter().count()
+        ),
+        if pause_state.by_debugger {
+            "PAUSED (F5 to resume, F6 to step)".to_string()
+        } else {
+            "Running (F5 to pause)".to_string()
+        },
+    ]);
+    // a symplectic integrator should keep both of these close to where they started
+    if *orbit_integrator == OrbitIntegrator::Leapfrog {
+        lines.extend([
+            format!("Orbits: leapfrog, {} asteroids", orbit_stats.bodies),
+            format!(
+                "  energy {:.4e}, drift {:+.4}%",
+                orbit_stats.energy,
+                orbit_stats.energy_drift() * 100.
+            ),
+            format!(
+                "  angular momentum {:.4e}, drift {:+.3e}",
+                orbit_stats.angular_momentum,
+                orbit_stats.angular_momentum_drift()
+            ),
+        ]);
+    }
+    lines.push(String::new());
+
+    match inspector.selected {
+        None => lines.push("Click a body to inspect it".to_string()),
+        Some(entity) => match inspected.body_query.get(entity) {
+            Ok((tf, mass_props, velocity, ext_force)) => {
+                let kind = if inspected.ships.contains(entity) {
+                    "ship"
+                } else if inspected.enemies.contains(entity) {
+                    "enemy"
+                } else if inspected.asteroids.contains(entity) {
+                    "asteroid"
+                } else if inspected.lasers.contains(entity) {
+                    "laser"
+                } else if inspected.missiles.contains(entity) {
+                    "missile"
+                } else {
+                    "body"
+                };
+                lines.push(format!("Selected: {kind} {entity:?}"));
+                lines.push(format!(
+                    "  Position: ({:.0}, {:.0})",
+                    tf.translation.x, tf.translation.y
+                ));
+                lines.push(format!("  Mass: {:.1}", mass_props.0.mass));
+                if let Some(velocity) = velocity {
+                    lines.push(format!(
+                        "  Velocity: ({:.1}, {:.1}) {:.1}/s, spin {:.2} rad/s",
+                        velocity.linvel.x,
+                        velocity.linvel.y,
+                        velocity.linvel.length(),
+                        velocity.angvel
+                    ));
+                }
+                if let Some(ext_force) = ext_force {
+                    lines.push(format!(
+                        "  Force: ({:.1}, {:.1}), torque {:.1}",
+                        ext_force.force.x, ext_force.force.y, ext_force.torque
+                    ));
+                }
+            }
+            Err(_) => lines.push(format!("Selected: {entity:?} is gone")),
+        },
+    }
+
+    let value = lines.join("\n");
+    for (mut text, _) in text_query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{components::PlayerId, controls::PauseState, MAX_PLAYERS};
+use bevy::{input::InputSystem, prelude::*, utils::HashMap};
+
+pub struct DevicesPlugin;
+
+impl Plugin for DevicesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DeviceManager::default())
+            .add_startup_system(device_overlay_setup_system)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                gamepad_connection_system.after(InputSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                gamepad_join_system.after(gamepad_connection_system),
+            )
+            .add_system(device_pause_system)
+            .add_system(device_overlay_system);
+    }
+}
+
+/// The devices driving one player.
+#[derive(Clone, Debug, Default)]
+pub struct PlayerDevices {
+    pub keyboard: bool, // keyboard and mouse
+    pub gamepad: Option<Gamepad>,
+    /// the pad that dropped out and its name, kept until the player is reclaimed
+    pub lost: Option<(Gamepad, String)>,
+}
+
+impl PlayerDevices {
+    pub fn waiting(&self) -> bool {
+        self.lost.is_some()
+    }
+}
+
+/// Remembers which device belongs to which player. The keyboard starts on the first
+/// player, who also picks up the first gamepad. Anyone else joins by pressing a button
+/// on an unassigned pad, and the keyboard joins as a player of its own the same way
+/// once it shares one with a pad.
+#[derive(Resource)]
+pub struct DeviceManager {
+    pub players: HashMap<PlayerId, PlayerDevices>,
+    names: HashMap<Gamepad, String>, // every connected pad
+}
+
+impl Default for DeviceManager {
+    fn default() -> Self {
+        Self {
+            players: HashMap::from([(
+                PlayerId(0),
+                PlayerDevices {
+                    keyboard: true,
+                    ..Default::default()
+                },
+            )]),
+            names: HashMap::default(),
+        }
+    }
+}
+
+impl DeviceManager {
+    pub fn player_for(&self, gamepad: Gamepad) -> Option<PlayerId> {
+        self.players
+            .iter()
+            .find(|(_, devices)| devices.gamepad == Some(gamepad))
+            .map(|(id, _)| *id)
+    }
+
+    /// connected pads that no player has picked up yet
+    pub fn unassigned(&self) -> Vec<Gamepad> {
+        let mut unassigned: Vec<Gamepad> = self
+            .names
+            .keys()
+            .filter(|gamepad| self.player_for(**gamepad).is_none())
+            .copied()
+            .collect();
+        unassigned.sort_by_key(|gamepad| gamepad.id);
+        unassigned
+    }
+
+    /// players paused until their pad comes back, first player first
+    pub fn waiting(&self) -> Vec<PlayerId> {
+        let mut waiting: Vec<PlayerId> = self
+            .players
+            .iter()
+            .filter(|(_, devices)| devices.waiting())
+            .map(|(id, _)| *id)
+            .collect();
+        waiting.sort();
+        waiting
+    }
+
+    /// the player a new pad joins as, the first player if they have no pad yet
+    pub fn join_slot(&self) -> Option<PlayerId> {
+        if self
+            .players
+            .get(&PlayerId(0))
+            .is_none_or(|devices| devices.gamepad.is_none())
+        {
+            return Some(PlayerId(0));
+        }
+        self.free_slot()
+    }
+
+    fn free_slot(&self) -> Option<PlayerId> {
+        (0..MAX_PLAYERS)
+            .map(PlayerId)
+            .find(|id| !self.players.contains_key(id))
+    }
+
+    /// the player the keyboard can join as, if it shares its player with a pad
+    pub fn keyboard_join_slot(&self) -> Option<PlayerId> {
+        let shared = self.players.values().any(|devices| {
+            devices.keyboard && (devices.gamepad.is_some() || devices.lost.is_some())
+        });
+        if shared {
+            self.free_slot()
+        } else {
+            None
+        }
+    }
+
+    /// leaves the pad with the player it was on and starts a new one on the keyboard
+    fn assign_keyboard(&mut self, id: PlayerId) {
+        for devices in self.players.values_mut() {
+            devices.keyboard = false;
+        }
+        self.players.entry(id).or_default().keyboard = true;
+        info!("keyboard assigned to {}", id.label());
+    }
+
+    fn assign(&mut self, id: PlayerId, gamepad: Gamepad) {
+        let devices = self.players.entry(id).or_default();
+        devices.gamepad = Some(gamepad);
+        devices.lost = None;
+        info!("gamepad {:?} assigned to {}", gamepad, id.label());
+    }
+
+    /// gives up on a lost pad, keyboard players carry on and the rest leave the game
+    fn drop_waiting(&mut self, id: PlayerId) {
+        match self.players.get_mut(&id) {
+            Some(devices) if devices.keyboard => devices.lost = None,
+            Some(_) => {
+                self.players.remove(&id);
+                info!("{} left the game", id.label());
+            }
+            None => {}
+        }
+    }
+}
+
+fn gamepad_connection_system(
+    mut device_manager: ResMut<DeviceManager>,
+    mut gamepad_evr: EventReader<GamepadEvent>,
+) {
+    for ev in gamepad_evr.iter() {
+        let gamepad = ev.gamepad;
+        match &ev.event_type {
+            GamepadEventType::Connected(info) => {
+                info!("gamepad {:?} connected: {}", gamepad, info.name);
+                device_manager.names.insert(gamepad, info.name.clone());
+
+                // the same pad coming back, or one with the same name if that is unambiguous
+                let waiting = device_manager.waiting();
+                let same_id = waiting.iter().find(|id| {
+                    device_manager.players[*id]
+                        .lost
+                        .as_ref()
+                        .is_some_and(|(lost, _)| *lost == gamepad)
+                });
+                let same_name: Vec<&PlayerId> = waiting
+                    .iter()
+                    .filter(|id| {
+                        device_manager.players[*id]
+                            .lost
+                            .as_ref()
+                            .is_some_and(|(_, name)| *name == info.name)
+                    })
+                    .collect();
+                let reclaimed = match (same_id, same_name.as_slice()) {
+                    (Some(id), _) => Some(*id),
+                    (None, [id]) => Some(**id),
+                    _ => None,
+                };
+
+                if let Some(id) = reclaimed {
+                    device_manager.assign(id, gamepad);
+                } else if waiting.is_empty() && device_manager.join_slot() == Some(PlayerId(0)) {
+                    // the first pad drives the first player straight away
+                    device_manager.assign(PlayerId(0), gamepad);
+                }
+            }
+            GamepadEventType::Disconnected => {
+                warn!("lost gamepad {gamepad:?}");
+                let name = device_manager.names.remove(&gamepad).unwrap_or_default();
+
+                // the player keeps their slot and waits for a pad to reclaim it
+                if let Some(id) = device_manager.player_for(gamepad) {
+                    let devices = device_manager.players.get_mut(&id).unwrap();
+                    devices.gamepad = None;
+                    devices.lost = Some((gamepad, name));
+                }
+            }
+            // other events are irrelevant
+            _ => {}
+        }
+    }
+}
+
+/// A button released on an unassigned pad reclaims a waiting player or joins a new
+/// one. Releases are used so the press that joined is not read as an action as well.
+pub fn gamepad_join_system(
+    kb: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    pause_state: Res<PauseState>,
+    mut device_manager: ResMut<DeviceManager>,
+) {
+    let joining: Vec<Gamepad> = buttons
+        .get_just_released()
+        .filter(|button| {
+            matches!(
+                button.button_type,
+                GamepadButtonType::South | GamepadButtonType::Start
+            )
+        })
+        .map(|button| button.gamepad)
+        .filter(|gamepad| device_manager.unassigned().contains(gamepad))
+        .collect();
+
+    for gamepad in joining {
+        let slot = match device_manager.waiting().first() {
+            Some(id) => Some(*id),
+            None => device_manager.join_slot(),
+        };
+        match slot {
+            Some(id) => device_manager.assign(id, gamepad),
+            None => warn!("gamepad {gamepad:?} cannot join, every player has one"),
+        }
+    }
+
+    // enter on the keyboard is its A / Start, unless a menu has it
+    if kb.just_released(KeyCode::Return) && !pause_state.in_menu {
+        if let Some(id) = device_manager.keyboard_join_slot() {
+            device_manager.assign_keyboard(id);
+        }
+    }
+
+    // carry on without the pads that dropped out
+    if kb.just_pressed(KeyCode::Back) {
+        for id in device_manager.waiting() {
+            device_manager.drop_waiting(id);
+        }
+    }
+}
+
+fn device_pause_system(device_manager: Res<DeviceManager>, mut pause_state: ResMut<PauseState>) {
+    let waiting = !device_manager.waiting().is_empty();
+    // only write on change so the pause is not re-applied every frame
+    if pause_state.waiting_for_device != waiting {
+        pause_state.waiting_for_device = waiting;
+    }
+}
+
+#[derive(Component)]
+struct DeviceOverlay;
+
+fn device_overlay_setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Black.ttf"),
+                    font_size: 28.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Percent(25.0),
+                    left: Val::Percent(20.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        )
+        .insert(DeviceOverlay);
+}
+
+/// Tells waiting players how to reclaim and unassigned pads how to join.
+fn device_overlay_system(
+    device_manager: Res<DeviceManager>,
+    mut query: Query<&mut Text, With<DeviceOverlay>>,
+) {
+    let mut lines = Vec::new();
+
+    let waiting = device_manager.waiting();
+    for id in waiting.iter() {
+        let (_, name) = device_manager.players[id].lost.as_ref().unwrap();
+        lines.push(format!("{} lost their controller ({name})", id.label()));
+    }
+    if !waiting.is_empty() {
+        lines.push("Press A / Start on a controller to reclaim".to_string());
+        lines.push("or Backspace to carry on without it".to_string());
+    } else {
+        if !device_manager.unassigned().is_empty() {
+            if let Some(id) = device_manager.join_slot() {
+                lines.push(format!("Press A / Start to join as {}", id.label()));
+            }
+        }
+        if let Some(id) = device_manager.keyboard_join_slot() {
+            lines.push(format!(
+                "Press Enter to play the keyboard as {}",
+                id.label()
+            ));
+        }
+    }
+
+    let value = lines.join("\n");
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::f32::consts::PI;
 
 use crate::{
-    components::{Enemy, FromEnemy, Laser, Movable, Orientation, Player, SpriteSize, Velocity},
-    EnemyCount, GameTextures, WinSize, BASE_SPEED, ENEMY_LASER_SIZE, ENEMY_MAX, ENEMY_SIZE,
+    components::{DespawnOutOfBounds, Enemy, EnemyKind, FromEnemy, Laser, Lifetime, Movable, Orientation, Player, SpriteSize, Velocity},
+    spatial::{SpatialIndex, SpatialLayer},
+    telemetry::{SystemTimings, Timed},
+    EnemyCount, GameTextures, WinSize, WorldBounds, BASE_SPEED, ENEMY_LASER_LIFETIME, ENEMY_LASER_SIZE, ENEMY_MAX, ENEMY_SIZE,
     SPRITE_SCALE, TIME_STEP,
 };
 use bevy::{ecs::schedule::ShouldRun, prelude::*, time::FixedTimestep};
@@ -27,7 +29,9 @@ fn enemy_spawn_system(
     game_textures: Res<GameTextures>,
     mut enemy_count: ResMut<EnemyCount>,
     win_size: Res<WinSize>,
+    timings: Res<SystemTimings>,
 ) {
+    let _timer = timings.start(Timed::Spawn);
     if enemy_count.0 < ENEMY_MAX {
         // compute the x/y
         /* 
@@ -49,7 +53,9 @@ fn enemy_spawn_system(
                 ..Default::default()
             })
             .insert(Enemy)
-            .insert(SpriteSize::from(ENEMY_SIZE));
+            .insert(EnemyKind::Invader)
+            .insert(SpriteSize::from(ENEMY_SIZE))
+            .insert(Velocity { x: 0., y: 0. });
 
         enemy_count.0 += 1;
     }
@@ -69,14 +75,24 @@ fn enemy_fire_criteria() -> ShouldRun {
 fn enemy_fire_system(
     mut commands: Commands,
     game_textures: Res<GameTextures>,
+    world_bounds: Res<WorldBounds>,
+    spatial_index: Res<SpatialIndex>,
     enemy_query: Query<&Transform, With<Enemy>>,
     player_query: Query<&Transform, With<Player>>,
 ) {
-    for &tf in enemy_query.iter() {
-        let (x, y) = (tf.translation.x, tf.translation.y);
-        for player_tf in player_query.iter() {
+    let world = world_bounds.rect();
+    for player_tf in player_query.iter() {
+        let player_x = player_tf.translation.x;
+        // only enemies in a strip up and down the world through the player can be lined up
+        let strip = Rect::new(player_x - 5., world.min.y, player_x + 5., world.max.y);
+        let enemies = spatial_index
+            .overlapping(SpatialLayer::Enemy, strip)
+            .filter_map(|enemy| enemy_query.get(enemy.entity).ok());
+
+        for tf in enemies {
+            let (x, y) = (tf.translation.x, tf.translation.y);
             let range = (x - 5.)..(x + 5.);
-            if range.contains(&player_tf.translation.x) {
+            if range.contains(&player_x) {
                 // spawn enemy laser sprite
                 commands
                     .spawn(SpriteBundle {
@@ -93,7 +109,9 @@ fn enemy_fire_system(
                     .insert(FromEnemy)
                     .insert(Movable { auto_despawn: true })
                     .insert(Velocity { x: 0., y: -1.5 })
-                    .insert(Orientation::default());
+                    .insert(Orientation::default())
+                    .insert(Lifetime::from_seconds(ENEMY_LASER_LIFETIME))
+                    .insert(DespawnOutOfBounds);
             }
         }
     }
@@ -0,0 +1,392 @@
+use crate::{
+    camera::{camera_follow_system, GameCamera},
+    components::{GravitySource, NoGhost},
+    WinSize, WorldBounds, EXTRA_GRAVITY, FIELD_ARROW_SPACING, FIELD_CELL_SIZE, FIELD_CONTOUR_STEP,
+    G, GRAVITY_SOURCE_MIN_DISTANCE,
+};
+use bevy::{
+    ecs::system::SystemParam,
+    math::Vec3Swizzles,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    sprite::Anchor,
+    transform::TransformSystem,
+};
+use bevy_rapier2d::prelude::ReadMassProperties;
+use std::f32::consts::FRAC_PI_2;
+
+pub struct FieldPlugin;
+
+impl Plugin for FieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FieldOverlay::default())
+            .add_startup_system(field_setup_system)
+            .add_system(field_toggle_system)
+            // once the camera has settled, so the overlay covers what it can see
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                field_overlay_system
+                    .after(camera_follow_system)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+// just in front of the far plane of the 2d camera, behind everything else
+const HEATMAP_Z: f32 = -0.08;
+const ARROW_Z: f32 = -0.04;
+
+/// Draws the gravitational field of the same masses `apply_gravitational_forces` uses.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FieldOverlay {
+    #[default]
+    Off,
+    /// a grid of arrows along the pull, longer and hotter where it is stronger
+    Vectors,
+    /// a heatmap of how deep the potential is, with equipotential contours
+    Potential,
+}
+
+impl FieldOverlay {
+    pub fn next(&self) -> Self {
+        match self {
+            FieldOverlay::Off => FieldOverlay::Vectors,
+            FieldOverlay::Vectors => FieldOverlay::Potential,
+            FieldOverlay::Potential => FieldOverlay::Off,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FieldOverlay::Off => "Off",
+            FieldOverlay::Vectors => "Vectors",
+            FieldOverlay::Potential => "Potential",
+        }
+    }
+}
+
+/// The sprite the potential is drawn into, one texel per grid cell over the view.
+#[derive(Component)]
+struct FieldHeatmap;
+
+/// One arrow of the vector grid, reused from frame to frame and hidden while spare.
+#[derive(Component)]
+struct FieldArrow;
+
+/// The white arrow every `FieldArrow` is a tinted and stretched copy of.
+#[derive(Resource)]
+struct ArrowTexture(Handle<Image>);
+
+fn field_setup_system(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    // resized to fit the view when first shown
+    let heatmap = images.add(Image::new_fill(
+        Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+    commands
+        .spawn(SpriteBundle {
+            texture: heatmap,
+            transform: Transform::from_xyz(0., 0., HEATMAP_Z),
+            visibility: Visibility::INVISIBLE,
+            ..Default::default()
+        })
+        .insert(FieldHeatmap)
+        .insert(NoGhost);
+
+    // pointing up, a shaft with a head on the top quarter
+    let (width, height) = (16, 64);
+    let mut arrow = Image::new_fill(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    for y in 0..height {
+        let half_width = if y < height / 4 {
+            (y as f32 + 1.) / (height / 4) as f32 * width as f32 / 2.
+        } else {
+            2.5
+        };
+        for x in 0..width {
+            if (x as f32 + 0.5 - width as f32 / 2.).abs() < half_width {
+                let i = ((y * width + x) * 4) as usize;
+                arrow.data[i..i + 4].copy_from_slice(&[255, 255, 255, 255]);
+            }
+        }
+    }
+    commands.insert_resource(ArrowTexture(images.add(arrow)));
+}
+
+/// F2 steps through the overlays.
+fn field_toggle_system(kb: Res<Input<KeyCode>>, mut overlay: ResMut<FieldOverlay>) {
+    if kb.just_pressed(KeyCode::F2) {
+        *overlay = overlay.next();
+    }
+}
+
+/// Pull towards, and depth of the potential well of, every mass at `point`. Like the
+/// gravity sources, masses are softened close in so the overlay stays readable there.
+fn sample(world_bounds: &WorldBounds, masses: &[(Vec2, f32)], point: Vec2) -> (Vec2, f32) {
+    let mut acceleration = Vec2::ZERO;
+    let mut depth = 0.;
+    for (position, mass) in masses {
+        let offset = world_bounds.offset(point, *position);
+        let distance = offset.length().max(GRAVITY_SOURCE_MIN_DISTANCE);
+        let strength = mass * G * EXTRA_GRAVITY;
+        acceleration += offset.normalize_or_zero() * strength / distance.powi(2);
+        depth += strength / distance;
+    }
+    (acceleration, depth)
+}
+
+/// Spacing in world units of about `pixels` on screen, kept to a power of two times
+/// `pixels` so the grid does not slide around as the camera zooms.
+fn snapped_spacing(pixels: f32, zoom: f32) -> f32 {
+    pixels * zoom.log2().round().exp2()
+}
+
+/// Where `value` sits between `low` and `high`, from 0. to 1.
+fn normalise(value: f32, low: f32, high: f32) -> f32 {
+    if high > low {
+        ((value - low) / (high - low)).clamp(0., 1.)
+    } else {
+        1.
+    }
+}
+
+/// The masses the overlay shows the pull of, and where the camera is looking.
+#[derive(SystemParam)]
+struct FieldView<'w, 's> {
+    win_size: Res<'w, WinSize>,
+    world_bounds: Res<'w, WorldBounds>,
+    camera_query:
+        Query<'w, 's, (&'static Transform, &'static OrthographicProjection), With<GameCamera>>,
+    body_query: Query<'w, 's, (&'static Transform, &'static ReadMassProperties)>,
+    source_query: Query<'w, 's, (&'static Transform, &'static GravitySource)>,
+}
+
+impl FieldView<'_, '_> {
+    fn masses(&self) -> Vec<(Vec2, f32)> {
+        self.body_query
+            .iter()
+            .map(|(tf, mass_props)| (tf.translation.xy(), mass_props.0.mass))
+            .chain(
+                self.source_query
+                    .iter()
+                    .map(|(tf, source)| (tf.translation.xy(), source.mass)),
+            )
+            .collect()
+    }
+
+    /// (bottom left, top right, zoom) of what the camera sees
+    fn view(&self) -> Option<(Vec2, Vec2, f32)> {
+        let (camera_tf, projection) = self.camera_query.get_single().ok()?;
+        let half_view = Vec2::new(self.win_size.w, self.win_size.h) / 2. * projection.scale;
+        let centre = camera_tf.translation.xy();
+        Some((centre - half_view, centre + half_view, projection.scale))
+    }
+}
+
+// kept apart from everything `FieldView` reads the transform of
+type HeatmapQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static Handle<Image>,
+        &'static mut Visibility,
+    ),
+    (
+        With<FieldHeatmap>,
+        Without<GameCamera>,
+        Without<ReadMassProperties>,
+        Without<GravitySource>,
+    ),
+>;
+
+type ArrowQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static mut Visibility,
+    ),
+    (
+        With<FieldArrow>,
+        Without<FieldHeatmap>,
+        Without<GameCamera>,
+        Without<ReadMassProperties>,
+        Without<GravitySource>,
+    ),
+>;
+
+fn field_overlay_system(
+    mut commands: Commands,
+    overlay: Res<FieldOverlay>,
+    arrow_texture: Res<ArrowTexture>,
+    mut images: ResMut<Assets<Image>>,
+    field: FieldView,
+    mut heatmap_query: HeatmapQuery,
+    mut arrow_query: ArrowQuery,
+) {
+    let world_bounds = &field.world_bounds;
+    let masses = field.masses();
+    let view = field.view().filter(|_| !masses.is_empty());
+
+    // potential heatmap
+    for (mut heatmap_tf, mut sprite, handle, mut visibility) in heatmap_query.iter_mut() {
+        let (view_min, view_max, zoom) = match view {
+            Some(view) if *overlay == FieldOverlay::Potential => view,
+            _ => {
+                if visibility.is_visible {
+                    visibility.is_visible = false;
+                }
+                continue;
+            }
+        };
+        let image = match images.get_mut(handle) {
+            Some(image) => image,
+            None => continue,
+        };
+
+        // texels line up with a grid fixed in the world, so the contours hold still
+        let cell = snapped_spacing(FIELD_CELL_SIZE, zoom);
+        let min = (view_min / cell).floor() * cell;
+        let cells = ((view_max - min) / cell).ceil().as_uvec2();
+        let size = Extent3d {
+            width: cells.x,
+            height: cells.y,
+            depth_or_array_layers: 1,
+        };
+        if image.texture_descriptor.size != size {
+            image.resize(size);
+        }
+
+        // the depth as a log, masses span too many orders of magnitude otherwise
+        let (width, height) = (cells.x as usize, cells.y as usize);
+        let levels: Vec<f32> = (0..width * height)
+            .map(|i| {
+                // texture rows run top to bottom
+                let texel = Vec2::new((i % width) as f32, (height - 1 - i / width) as f32);
+                let (_, depth) = sample(world_bounds, &masses, min + (texel + 0.5) * cell);
+                depth.max(f32::MIN_POSITIVE).ln()
+            })
+            .collect();
+        let low = levels.iter().copied().fold(f32::INFINITY, f32::min);
+        let high = levels.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+        for (i, level) in levels.iter().enumerate() {
+            // a contour wherever the next texel right or down is past another step
+            let band = (level / FIELD_CONTOUR_STEP).floor();
+            let crosses = |j: usize| (levels[j] / FIELD_CONTOUR_STEP).floor() != band;
+            let contour = (i % width + 1 < width && crosses(i + 1))
+                || (i / width + 1 < height && crosses(i + width));
+
+            let rgba = if contour {
+                [255, 255, 255, 140]
+            } else {
+                // shallow blue to deep orange
+                let t = normalise(*level, low, high);
+                [
+                    (25. + 230. * t) as u8,
+                    (25. + 100. * t) as u8,
+                    (100. * (1. - t)) as u8,
+                    (40. + 90. * t) as u8,
+                ]
+            };
+            image.data[i * 4..i * 4 + 4].copy_from_slice(&rgba);
+        }
+
+        let extent = cells.as_vec2() * cell;
+        heatmap_tf.translation = (min + extent / 2.).extend(HEATMAP_Z);
+        sprite.custom_size = Some(extent);
+        visibility.is_visible = true;
+    }
+
+    // vector grid, as (grid point, pull) for every point in view
+    let mut pulls = Vec::new();
+    let mut spacing = 0.;
+    if let Some((view_min, view_max, zoom)) = view.filter(|_| *overlay == FieldOverlay::Vectors) {
+        spacing = snapped_spacing(FIELD_ARROW_SPACING, zoom);
+        let first = (view_min / spacing).ceil().as_ivec2();
+        let last = (view_max / spacing).floor().as_ivec2();
+        for x in first.x..=last.x {
+            for y in first.y..=last.y {
+                let point = IVec2::new(x, y).as_vec2() * spacing;
+                let (acceleration, _) = sample(world_bounds, &masses, point);
+                if acceleration != Vec2::ZERO {
+                    pulls.push((point, acceleration));
+                }
+            }
+        }
+    }
+    let strength = |acceleration: &Vec2| acceleration.length().ln();
+    let low = pulls
+        .iter()
+        .map(|(_, a)| strength(a))
+        .fold(f32::INFINITY, f32::min);
+    let high = pulls
+        .iter()
+        .map(|(_, a)| strength(a))
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    // each arrow as (transform, size, colour)
+    let arrows = pulls.into_iter().map(|(point, acceleration)| {
+        let t = normalise(strength(&acceleration), low, high);
+        let length = spacing * (0.3 + 0.6 * t);
+        let transform = Transform::from_translation(point.extend(ARROW_Z)).with_rotation(
+            Quat::from_rotation_z(acceleration.y.atan2(acceleration.x) - FRAC_PI_2),
+        );
+        let size = Vec2::new(length / 4., length);
+        (
+            transform,
+            size,
+            Color::rgba(0.3 + 0.7 * t, 0.8 - 0.4 * t, 1. - t, 0.7),
+        )
+    });
+
+    let mut arrows = arrows.into_iter();
+    for (mut arrow_tf, mut sprite, mut visibility) in arrow_query.iter_mut() {
+        match arrows.next() {
+            Some((transform, size, color)) => {
+                *arrow_tf = transform;
+                sprite.custom_size = Some(size);
+                sprite.color = color;
+                visibility.is_visible = true;
+            }
+            None => {
+                if visibility.is_visible {
+                    visibility.is_visible = false;
+                }
+            }
+        }
+    }
+    for (transform, size, color) in arrows {
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(size),
+                    anchor: Anchor::BottomCenter,
+                    ..Default::default()
+                },
+                texture: arrow_texture.0.clone(),
+                transform,
+                ..Default::default()
+            })
+            .insert(FieldArrow)
+            .insert(NoGhost);
+    }
+}
@@ -0,0 +1,119 @@
+use std::f32::consts::PI;
+
+use crate::{
+    components::{
+        AreaDamage, Enemy, ExplosionToSpawn, FromPlayer, GravitySource, GravityWell, Orientation,
+        Player, PlayerId, Velocity,
+    },
+    controls::{Action, ActionState},
+    WorldBounds, ENEMY_WELL_DAMPING, EXTRA_GRAVITY, G, GRAVITY_SOURCE_MIN_DISTANCE,
+    GRAVITY_WELL_COLLAPSE_RADIUS, GRAVITY_WELL_DEPLOY_DISTANCE, GRAVITY_WELL_LIFETIME,
+    GRAVITY_WELL_MASS,
+};
+use bevy::{math::Vec3Swizzles, prelude::*};
+
+pub struct GravityWellPlugin;
+
+impl Plugin for GravityWellPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(gravity_well_deploy_system)
+            .add_system(gravity_well_collapse_system)
+            .add_system(gravity_well_enemy_pull_system);
+    }
+}
+
+fn gravity_well_deploy_system(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    player_query: Query<(&PlayerId, &Transform, &Orientation), With<Player>>,
+    well_query: Query<&FromPlayer, With<GravityWell>>,
+) {
+    for (id, player_tf, orientation) in player_query.iter() {
+        if !actions.player(*id).just_pressed(Action::GravityWell) {
+            continue;
+        }
+
+        // only one well at a time for each player
+        if well_query.iter().any(|from_player| from_player.0 == *id) {
+            continue;
+        }
+
+        let theta = orientation.theta;
+        let heading = Vec2::new(-theta.sin(), theta.cos());
+        let position = player_tf.translation.xy() + heading * GRAVITY_WELL_DEPLOY_DISTANCE;
+
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.35, 0., 0.6, 0.9),
+                    custom_size: Some(Vec2::new(40., 40.)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(position.extend(5.)),
+                ..Default::default()
+            })
+            .insert(GravityWell {
+                collapse: Timer::from_seconds(GRAVITY_WELL_LIFETIME, TimerMode::Once),
+            })
+            .insert(GravitySource {
+                mass: GRAVITY_WELL_MASS,
+            })
+            .insert(FromPlayer(*id));
+    }
+}
+
+/// Spins the well down as it runs out of time, then collapses it into a blast.
+fn gravity_well_collapse_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut GravityWell, &FromPlayer)>,
+) {
+    for (entity, mut transform, mut well, from_player) in query.iter_mut() {
+        well.collapse.tick(time.delta());
+
+        let remaining = 1. - well.collapse.percent();
+        transform.rotate_z(2. * PI * time.delta_seconds() / remaining.max(0.1));
+        transform.scale = Vec3::splat(remaining.max(0.1));
+
+        if well.collapse.finished() {
+            commands.entity(entity).despawn();
+            commands.spawn((
+                ExplosionToSpawn(transform.translation),
+                AreaDamage {
+                    radius: GRAVITY_WELL_COLLAPSE_RADIUS,
+                    owner: Some(from_player.0),
+                },
+            ));
+        }
+    }
+}
+
+/// Enemies are not rapier bodies, so drift them towards gravity sources by hand.
+fn gravity_well_enemy_pull_system(
+    time: Res<Time>,
+    world_bounds: Res<WorldBounds>,
+    source_query: Query<(&Transform, &GravitySource), Without<Enemy>>,
+    mut enemy_query: Query<(&mut Transform, &mut Velocity), With<Enemy>>,
+) {
+    let dt = time.delta_seconds();
+
+    for (mut enemy_tf, mut velocity) in enemy_query.iter_mut() {
+        let position = enemy_tf.translation.xy();
+
+        // acceleration is independent of the enemy's mass
+        let mut acceleration = Vec2::ZERO;
+        for (source_tf, source) in source_query.iter() {
+            let offset = world_bounds.offset(position, source_tf.translation.xy());
+            let distance_squared = offset
+                .length_squared()
+                .max(GRAVITY_SOURCE_MIN_DISTANCE.powi(2));
+            acceleration +=
+                offset.normalize_or_zero() * source.mass * G * EXTRA_GRAVITY / distance_squared;
+        }
+
+        velocity.x = (velocity.x + acceleration.x * dt) * ENEMY_WELL_DAMPING;
+        velocity.y = (velocity.y + acceleration.y * dt) * ENEMY_WELL_DAMPING;
+        enemy_tf.translation.x += velocity.x * dt;
+        enemy_tf.translation.y += velocity.y * dt;
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    components::{Explosion, Player, PlayerId},
+    controls::{Action, ActionState, PauseState},
+    devices::DeviceManager,
+    Players, WorldBounds, RUMBLE_DAMAGE, RUMBLE_EXPLOSION, RUMBLE_EXPLOSION_RADIUS, RUMBLE_THRUST,
+};
+use bevy::{ecs::system::SystemParam, math::Vec3Swizzles, prelude::*, utils::HashMap};
+use gilrs::{
+    ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat},
+    Gilrs,
+};
+use std::marker::PhantomData;
+
+pub struct HapticsPlugin;
+
+impl Plugin for HapticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Haptics::default())
+            .insert_resource(HapticsSettings::default())
+            .add_event::<RumbleEvent>()
+            .add_system(damage_rumble_system)
+            .add_system(explosion_rumble_system)
+            .add_system(
+                rumble_output_system
+                    .after(damage_rumble_system)
+                    .after(explosion_rumble_system),
+            );
+    }
+}
+
+/// A short buzz on one player's pad.
+pub struct RumbleEvent {
+    pub player: PlayerId,
+    pub strength: f32, // 0. to 1., before the intensity setting
+    pub duration: f32, // seconds
+}
+
+#[derive(Resource, Default)]
+pub struct Haptics {
+    pulses: HashMap<PlayerId, Vec<(f32, f64)>>, // strength and when it ends
+    sent: HashMap<Gamepad, f32>,                // last strength handed to the motors
+}
+
+/// Drives the motors through gilrs force feedback, which bevy's own input leaves out.
+/// Each pad gets one endless effect at full strength that is played with its gain set to
+/// the strength wanted. Pads without motors have no effect and are left alone.
+#[derive(Default)]
+struct GilrsRumble {
+    effects: HashMap<Gamepad, Option<Effect>>,
+}
+
+impl GilrsRumble {
+    fn set(&mut self, gilrs: &mut Gilrs, gamepad: Gamepad, strength: f32) {
+        let effect = self
+            .effects
+            .entry(gamepad)
+            .or_insert_with(|| rumble_effect(gilrs, gamepad));
+        let effect = match effect {
+            Some(effect) => effect,
+            None => return,
+        };
+
+        let played = if strength > 0. {
+            effect.set_gain(strength).and_then(|_| effect.play())
+        } else {
+            effect.stop()
+        };
+        if let Err(err) = played {
+            warn!("could not rumble gamepad {}: {err}", gamepad.id);
+        }
+    }
+}
+
+fn rumble_effect(gilrs: &mut Gilrs, gamepad: Gamepad) -> Option<Effect> {
+    // bevy numbers its pads with gilrs' ids
+    let id = gilrs
+        .gamepads()
+        .find(|(id, pad)| usize::from(*id) == gamepad.id && pad.is_ff_supported())
+        .map(|(id, _)| id)?;
+
+    let motor = |kind| BaseEffect {
+        kind,
+        ..Default::default()
+    };
+    let effect = EffectBuilder::new()
+        .add_effect(motor(BaseEffectType::Strong {
+            magnitude: u16::MAX,
+        }))
+        .add_effect(motor(BaseEffectType::Weak {
+            magnitude: u16::MAX,
+        }))
+        .repeat(Repeat::Infinitely)
+        .gamepads(&[id])
+        .finish(gilrs);
+    match effect {
+        Ok(effect) => Some(effect),
+        Err(err) => {
+            warn!("no rumble on gamepad {}: {err}", gamepad.id);
+            None
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct HapticsSettings {
+    pub enabled: bool,
+    pub intensity: f32, // 0. to 1.
+}
+
+impl Default for HapticsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 1.,
+        }
+    }
+}
+
+/// A strong buzz whenever a player loses a life.
+fn damage_rumble_system(
+    players: Res<Players>,
+    mut last_shots: Local<HashMap<PlayerId, f64>>,
+    mut rumble: EventWriter<RumbleEvent>,
+) {
+    for (id, player_state) in players.0.iter() {
+        let last_shot = last_shots.entry(*id).or_insert(-1.);
+        if player_state.last_shot != *last_shot {
+            *last_shot = player_state.last_shot;
+            if player_state.last_shot != -1. {
+                let (strength, duration) = RUMBLE_DAMAGE;
+                rumble.send(RumbleEvent {
+                    player: *id,
+                    strength,
+                    duration,
+                });
+            }
+        }
+    }
+}
+
+/// Explosions shake every ship nearby, fading out with distance.
+fn explosion_rumble_system(
+    world_bounds: Res<WorldBounds>,
+    explosion_query: Query<&Transform, Added<Explosion>>,
+    player_query: Query<(&PlayerId, &Transform), With<Player>>,
+    mut rumble: EventWriter<RumbleEvent>,
+) {
+    let (strength, duration) = RUMBLE_EXPLOSION;
+
+    for explosion_tf in explosion_query.iter() {
+        for (id, player_tf) in player_query.iter() {
+            let distance = world_bounds
+                .offset(explosion_tf.translation.xy(), player_tf.translation.xy())
+                .length();
+            if distance < RUMBLE_EXPLOSION_RADIUS {
+                rumble.send(RumbleEvent {
+                    player: *id,
+                    strength: strength * (1. - distance / RUMBLE_EXPLOSION_RADIUS),
+                    duration,
+                });
+            }
+        }
+    }
+}
+
+/// Who has which pad, and what besides the pulses sets how hard it rumbles.
+#[derive(SystemParam)]
+struct RumbleInputs<'w, 's> {
+    settings: Res<'w, HapticsSettings>,
+    pause_state: Res<'w, PauseState>,
+    actions: Res<'w, ActionState>,
+    device_manager: Res<'w, DeviceManager>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// Mixes the pulses with the low hum of the main engine and hands the result to the
+/// motors, only when it changes.
+fn rumble_output_system(
+    time: Res<Time>,
+    inputs: RumbleInputs,
+    gamepads: Res<Gamepads>,
+    gilrs: Option<NonSendMut<Gilrs>>,
+    mut haptics: ResMut<Haptics>,
+    mut rumble: EventReader<RumbleEvent>,
+    mut motors: Local<GilrsRumble>,
+) {
+    let now = time.elapsed_seconds_f64();
+
+    for event in rumble.iter() {
+        haptics
+            .pulses
+            .entry(event.player)
+            .or_default()
+            .push((event.strength, now + event.duration as f64));
+    }
+    for pulses in haptics.pulses.values_mut() {
+        pulses.retain(|(_, ends)| *ends > now);
+    }
+
+    // without gilrs, as when it fails to start, there is nothing to drive
+    let mut gilrs = match gilrs {
+        Some(gilrs) => gilrs,
+        None => return,
+    };
+    // a pad that comes back is set up again
+    motors
+        .effects
+        .retain(|gamepad, _| gamepads.contains(*gamepad));
+    haptics
+        .sent
+        .retain(|gamepad, _| gamepads.contains(*gamepad));
+
+    for (id, devices) in inputs.device_manager.players.iter() {
+        let gamepad = match devices.gamepad {
+            Some(gamepad) => gamepad,
+            None => continue,
+        };
+
+        let strength = if inputs.settings.enabled && !inputs.pause_state.is_paused() {
+            let thrust = if inputs.actions.player(*id).pressed(Action::Thrust) {
+                RUMBLE_THRUST
+            } else {
+                0.
+            };
+            let pulse = haptics
+                .pulses
+                .get(id)
+                .and_then(|pulses| {
+                    pulses
+                        .iter()
+                        .map(|(strength, _)| *strength)
+                        .reduce(f32::max)
+                })
+                .unwrap_or(0.);
+            (thrust.max(pulse) * inputs.settings.intensity).clamp(0., 1.)
+        } else {
+            0.
+        };
+
+        if haptics.sent.get(&gamepad) != Some(&strength) {
+            haptics.sent.insert(gamepad, strength);
+            motors.set(&mut gilrs, gamepad, strength);
+        }
+    }
+}
@@ -0,0 +1,1615 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use bevy::{
+    ecs::system::SystemParam, math::Vec3Swizzles, prelude::*, tasks::ComputeTaskPool,
+    utils::HashSet,
+};
+use bevy::{
+    app::{ScheduleRunnerPlugin, ScheduleRunnerSettings},
+    render::settings::WgpuSettings,
+    winit::WinitPlugin,
+};
+use components::{
+    ActivePowerUps, AreaDamage, Asteroid, AsteroidHitScored, Enemy, EnemyKind, Explosion, ExplosionTimer, ExplosionToSpawn,
+    FromEnemy, FromPlayer, GravityAssist, GravitySource, Laser, Player, PlayerId,
+    PowerUpKind, SpriteSize, Velocity, WeaponHeat,
+};
+use bounds::BoundsPlugin;
+use calibration::CalibrationPlugin;
+use camera::{CameraPlugin, GameCamera};
+use controls::ControlsPlugin;
+use devices::DevicesPlugin;
+use debug::DebugPlugin;
+use enemy::EnemyPlugin;
+use field::FieldPlugin;
+use gravity_well::GravityWellPlugin;
+use haptics::HapticsPlugin;
+use missile::MissilePlugin;
+use orbit::OrbitPlugin;
+use player::PlayerPlugin;
+use powerup::PowerUpPlugin;
+use radar::RadarPlugin;
+use score::{Combos, ScoreEvent, ScorePlugin, ScoreReason};
+use settings::SettingsPlugin;
+use slingshot::SlingshotPlugin;
+use spatial::{sprite_rect, SpatialIndex, SpatialLayer, SpatialPlugin};
+use telemetry::{SystemTimings, Timed, TelemetryPlugin};
+
+use bevy_rapier2d::prelude::*;
+
+use std::{collections::HashMap, marker::PhantomData, time::Duration};
+
+use rand::prelude::*;
+
+
+//#[deny(warnings)]
+
+mod calibration;
+mod bounds;
+mod camera;
+pub mod components;
+mod controls;
+mod debug;
+mod devices;
+mod enemy;
+mod field;
+mod gravity_well;
+mod haptics;
+mod missile;
+mod orbit;
+mod player;
+mod powerup;
+mod radar;
+pub mod score;
+mod settings;
+mod slingshot;
+pub mod spatial;
+pub mod telemetry;
+
+// Asset Constants
+const PLAYER_SPRITE: &str = "player_b_01.png";
+const PLAYER_SIZE: (f32, f32) = (98., 75.);
+const SPRITE_SCALE: f32 = 0.5;
+const PLAYER_LASER_SPRITE: &str = "laser_a_01.png";
+const PLAYER_LASER_SIZE: (f32, f32) = (9., 54.);
+const ENEMY_SPRITE: &str = "enemy_a_01.png";
+const ENEMY_SIZE: (f32, f32) = (93., 84.);
+const ENEMY_LASER_SPRITE: &str = "laser_b_01.png";
+const ENEMY_LASER_SIZE: (f32, f32) = (17., 55.);
+const EXPLOSION: &str = "explo_a_sheet.png";
+const EXPLOSION_LENGTH: usize = 16;
+
+// game constants
+
+const TIME_STEP: f32 = 1. / 60.;
+const WINDOW_SIZE: (f32, f32) = (1080., 720.);
+const ASTEROID_COUNT: usize = 10;
+const BASE_SPEED: f32 = 500.;
+
+const PLAYER_RESPAWN_DELAY: f64 = 2.;
+const ENEMY_MAX: u32 = 0;
+const PLAYER_LIVES: u32 = 3;
+const MAX_PLAYERS: usize = 4; // local players, one per gamepad or the keyboard
+const PLAYER_SPAWN_SPACING: f32 = 160.; // gap between the ships along the bottom edge
+
+pub const G: f32 = 0.00000000006674;
+pub const EXTRA_GRAVITY: f32 = 1_000_000_000_000.; // gravity is this many times stronger than real
+const GRAVITY_BODIES_PER_TASK: usize = 64; // fewer bodies than this are not worth spreading over threads
+// asteroids can be moved by a leapfrog integrator instead of rapier, see `OrbitIntegrator`
+const ORBIT_SUBSTEP: f32 = 1. / 240.; // seconds per step of the integrator
+const ORBIT_MAX_SUBSTEPS: u32 = 16; // per frame, a slower frame falls behind rather than stalling
+const PRIMARY_THRUST: f32 = 100_000.; // main engine, left trigger or shift
+const SECONDARY_THRUST: f32 = 10_000.; // thumbstick adjustments
+const PLAYER_TURN_RATE: f32 = 3. * PI; // max radians per second the nose turns towards the aim
+// newtonian turning, a PD controller firing the ship's rotation thrusters
+const TURN_STIFFNESS: f32 = 60.; // angular acceleration per radian off the aim
+const TURN_DAMPING: f32 = 15.; // angular deceleration per radian per second of spin
+const TURN_MAX_ACCELERATION: f32 = 40.; // radians per second squared the thrusters can manage
+const ANGULAR_DAMPING: f32 = 1.; // lets a spin die down on its own
+
+const LASER_VELOCITY: f32 = 100.;
+const MUZZLE_DISTANCE: f32 = 45.; // lasers leave this far ahead of the ship's centre, clear of its hull
+
+const FIRE_RATE: f64 = 8.; // max shots per second
+const HEAT_PER_SHOT: f32 = 0.08;
+const HEAT_COOL_RATE: f32 = 0.35; // heat lost per second
+const OVERHEAT_RECOVER: f32 = 0.25; // overheated weapon unlocks below this heat
+
+const MISSILE_COOLDOWN: f64 = 1.;
+const MISSILE_FUEL: f32 = 4.; // seconds of thrust before detonating
+const MISSILE_THRUST: f32 = 3_000.;
+const MISSILE_TURN_RATE: f32 = PI; // max radians per second
+const MISSILE_SEEK_CONE: f32 = PI / 4.; // half angle either side of the nose
+const MISSILE_SEEK_RANGE: f32 = 600.;
+const MISSILE_PROXIMITY_FUSE: f32 = 30.;
+const MISSILE_BLAST_RADIUS: f32 = 120.;
+const BLAST_IMPULSE: f32 = 5_000.; // knockback on bodies caught in a blast
+
+const GRAVITY_WELL_MASS: f32 = 500_000.;
+const GRAVITY_WELL_LIFETIME: f32 = 4.; // seconds before collapsing
+const GRAVITY_WELL_DEPLOY_DISTANCE: f32 = 200.; // placed this far ahead of the ship
+const GRAVITY_WELL_COLLAPSE_RADIUS: f32 = 60.;
+pub const GRAVITY_SOURCE_MIN_DISTANCE: f32 = 20.;
+const ENEMY_WELL_DAMPING: f32 = 0.98; // enemy drift velocity kept per frame
+
+const ENEMY_DROP_CHANCE: f64 = 0.3;
+const PICKUP_LIFETIME: f32 = 10.;
+const PICKUP_RADIUS: f32 = 12.;
+const SHIELD_DURATION: f32 = 8.;
+const WEAPON_UPGRADE_DURATION: f32 = 10.;
+const WEAPON_UPGRADE_SPREAD: f32 = PI / 18.; // radians between the upgrade's three shots
+const THRUST_BOOST_DURATION: f32 = 8.;
+const THRUST_BOOST_FACTOR: f32 = 1.5;
+const SCORE_MULTIPLIER_DURATION: f32 = 15.;
+const SCORE_MULTIPLIER: u32 = 2;
+
+const COMBO_STEP: f32 = 0.25; // multiplier gained per scoring event
+const COMBO_MAX: f32 = 4.;
+const COMBO_WINDOW: f64 = 2.; // seconds before the combo starts to decay
+const COMBO_DECAY: f32 = 1.; // multiplier lost per second once decaying
+const SCORE_POPUP_LIFETIME: f32 = 1.;
+const ASTEROID_HIT_INTERVAL: f64 = 1.; // seconds before the same asteroid scores for a hit again
+
+const SLINGSHOT_MIN_ACCELERATION: f32 = 10.; // pull from a single body that counts as a close pass
+const SLINGSHOT_MIN_DELTA_V: f32 = 30.; // speed a pass must add to earn the bonus
+pub const CURVED_SHOT_MIN_ANGLE: f32 = PI / 6.; // how far gravity must bend a laser for a trick shot
+
+// the world is much bigger than the window, bodies are kept inside by `BoundaryMode`
+const WORLD_SIZE: (f32, f32) = (4000., 3000.);
+const SOFT_BOUNDARY_STIFFNESS: f32 = 2.; // acceleration per pixel past the edge
+const WALL_THICKNESS: f32 = 100.;
+const DESPAWN_MARGIN: f32 = 200.; // how far past the edge projectiles fly before despawning
+const LASER_LIFETIME: f32 = 4.;
+const ENEMY_LASER_LIFETIME: f32 = 6.;
+const GHOST_MARGIN: f32 = 100.; // sprites this far outside the view across a seam still get a ghost
+const SPATIAL_CELL_SIZE: f32 = 128.; // side of a cell in the spatial index, a few ships across
+
+const CONTROLS_CONFIG: &str = "config/controls.ron";
+const STICKS_CONFIG: &str = "config/sticks.ron"; // deadzones, curves and calibration
+
+// camera, zoom is the projection scale so above 1. shows more of the world
+const CAMERA_FOLLOW_RATE: f32 = 4.; // how quickly the camera catches up, per second
+const CAMERA_LOOK_AHEAD: f32 = 0.5; // seconds of velocity the camera leads the ships by
+const CAMERA_MAX_LOOK_AHEAD: f32 = 250.;
+const CAMERA_ZOOM_RATE: f32 = 2.;
+const CAMERA_MIN_ZOOM: f32 = 0.5;
+const CAMERA_MAX_ZOOM: f32 = 4.;
+const CAMERA_SPEED_ZOOM: f32 = 600.; // speed at which auto zoom has pulled back to twice the view
+const CAMERA_NEARBY_RANGE: f32 = 600.; // bodies this close to a ship are kept in view
+const CAMERA_FRAME_MARGIN: f32 = 150.; // space kept around whatever is framed
+const CAMERA_MANUAL_ZOOM_RATE: f32 = 2.; // zoom factor per second while a zoom button is held
+const CAMERA_SCROLL_ZOOM: f32 = 1.1; // zoom factor per line scrolled
+const SHAKE_MAX_OFFSET: f32 = 30.;
+const SHAKE_DECAY: f32 = 1.5; // trauma lost per second
+const SHAKE_EXPLOSION: f32 = 0.4; // trauma added by an explosion right on the focus
+const SHAKE_EXPLOSION_RADIUS: f32 = 800.;
+
+// radar
+const RADAR_SIZE: f32 = 180.; // pixels across
+const RADAR_RANGE: f32 = 2500.; // world distance from the middle to the edge of the radar
+const EDGE_INDICATOR_SIZE: f32 = 12.;
+const EDGE_INDICATOR_INSET: f32 = 20.; // how far in from the screen edge the markers sit
+
+// gravity field overlay
+const FIELD_ARROW_SPACING: f32 = 60.; // pixels between the arrows of the vector grid
+const FIELD_CELL_SIZE: f32 = 6.; // pixels per texel of the potential heatmap
+const FIELD_CONTOUR_STEP: f32 = 0.2; // equipotentials every time the log of the potential moves this much
+
+// telemetry
+const TELEMETRY_CSV: &str = "telemetry.csv";
+const TELEMETRY_INTERVAL: f32 = 1.; // seconds between rows of the csv
+const TELEMETRY_HISTORY: usize = 120; // frames the rolling stats are taken over
+
+// rumble as (strength, seconds), strength from 0. to 1.
+const RUMBLE_FIRE: (f32, f32) = (0.2, 0.08);
+const RUMBLE_MISSILE: (f32, f32) = (0.4, 0.15);
+const RUMBLE_DAMAGE: (f32, f32) = (1., 0.5);
+const RUMBLE_EXPLOSION: (f32, f32) = (0.8, 0.3); // at the centre of the blast
+const RUMBLE_EXPLOSION_RADIUS: f32 = 400.; // explosions further away are not felt
+const RUMBLE_THRUST: f32 = 0.15; // held while the main engine burns
+
+// Resources
+
+/// Read from the command line, see the README.
+#[derive(Resource, Clone, Debug)]
+pub struct LaunchOptions {
+    /// no window or rendering, and the physics steps a fixed `TIME_STEP` every frame
+    pub headless: bool,
+    /// where to write the telemetry csv, if anywhere
+    pub telemetry: Option<String>,
+    pub asteroids: usize,
+    /// quit after this many frames
+    pub frames: Option<u32>,
+}
+
+impl LaunchOptions {
+    fn from_args() -> Self {
+        let mut options = LaunchOptions {
+            headless: false,
+            telemetry: None,
+            asteroids: ASTEROID_COUNT,
+            frames: None,
+        };
+
+        let mut args = std::env::args().skip(1).peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless = true,
+                // the path is optional
+                "--telemetry" => {
+                    let path = args.next_if(|next| !next.starts_with("--"));
+                    options.telemetry = Some(path.unwrap_or_else(|| TELEMETRY_CSV.to_string()));
+                }
+                "--asteroids" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(asteroids) => options.asteroids = asteroids,
+                    None => eprintln!("--asteroids needs a number"),
+                },
+                "--frames" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(frames) => options.frames = Some(frames),
+                    None => eprintln!("--frames needs a number"),
+                },
+                _ => eprintln!("unknown argument {arg}"),
+            }
+        }
+
+        // a headless run is only any use for what it measures
+        if options.headless && options.telemetry.is_none() {
+            options.telemetry = Some(TELEMETRY_CSV.to_string());
+        }
+        options
+    }
+}
+
+#[derive(Resource)]
+pub struct WinSize {
+    pub w: f32,
+    pub h: f32,
+}
+
+/// What happens to bodies that reach the edge of the world.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BoundaryMode {
+    /// pushed back by a force that grows the further past the edge they are
+    #[default]
+    Soft,
+    /// bounce off fixed colliders around the edge
+    Walls,
+    /// leave one edge and come back in at the opposite one
+    Wrap,
+}
+
+impl BoundaryMode {
+    pub fn next(&self) -> Self {
+        match self {
+            BoundaryMode::Soft => BoundaryMode::Walls,
+            BoundaryMode::Walls => BoundaryMode::Wrap,
+            BoundaryMode::Wrap => BoundaryMode::Soft,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BoundaryMode::Soft => "Soft",
+            BoundaryMode::Walls => "Walls",
+            BoundaryMode::Wrap => "Wrap around",
+        }
+    }
+}
+
+/// The play field, centred on the origin and independent of the window size.
+#[derive(Resource, Clone, Copy)]
+pub struct WorldBounds {
+    pub size: Vec2,
+    pub mode: BoundaryMode,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self {
+            size: Vec2::new(WORLD_SIZE.0, WORLD_SIZE.1),
+            mode: BoundaryMode::default(),
+        }
+    }
+}
+
+impl WorldBounds {
+    pub fn rect(&self) -> Rect {
+        Rect::from_center_size(Vec2::ZERO, self.size)
+    }
+
+    /// the same position brought back inside the world
+    pub fn wrap(&self, position: Vec2) -> Vec2 {
+        let half = self.size / 2.;
+        Vec2::new(
+            (position.x + half.x).rem_euclid(self.size.x) - half.x,
+            (position.y + half.y).rem_euclid(self.size.y) - half.y,
+        )
+    }
+
+    /// from `from` to `to`, the short way across the seams when the world wraps
+    pub fn offset(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let offset = to - from;
+        match self.mode {
+            BoundaryMode::Wrap => self.wrap(offset),
+            _ => offset,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct GameTextures {
+    player: Handle<Image>,
+    player_laser: Handle<Image>,
+    enemy: Handle<Image>,
+    explosion: Handle<TextureAtlas>,
+    enemy_laser: Handle<Image>,
+}
+
+#[derive(Resource)]
+pub struct EnemyCount(pub u32);
+
+/// Entities the hit systems have despawned this frame. Despawns wait for the end of the
+/// stage, so this stops a second system counting the same enemy again, or taking another
+/// life off the same ship, in the meantime.
+#[derive(Resource, Default)]
+pub struct Despawned(pub HashSet<Entity>);
+
+/// Lives and score of one player.
+struct PlayerState {
+    on: bool,       // alive
+    last_shot: f64, // -1 if not shot
+    score: u32,
+    lives: u32,
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            on: false,
+            last_shot: -1.,
+            score: 0,
+            lives: 0,
+        }
+    }
+}
+
+impl PlayerState {
+    pub fn shot(&mut self, time: f64) {
+        self.on = false;
+        self.last_shot = time;
+        self.lives = self.lives.saturating_sub(1);
+    }
+
+    pub fn spawned(&mut self) {
+        self.on = true;
+        self.last_shot = -1.;
+    }
+}
+
+/// Every player that has joined, created when their ship first spawns.
+#[derive(Resource, Default)]
+struct Players(HashMap<PlayerId, PlayerState>);
+
+impl Players {
+    fn state(&mut self, id: PlayerId) -> &mut PlayerState {
+        self.0.entry(id).or_default()
+    }
+}
+
+/// Whether players fight the invaders together or each other as well.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    Coop,
+    /// player lasers and blasts hit other players
+    Versus,
+}
+
+impl GameMode {
+    pub fn next(&self) -> Self {
+        match self {
+            GameMode::Coop => GameMode::Versus,
+            GameMode::Versus => GameMode::Coop,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameMode::Coop => "Co-op",
+            GameMode::Versus => "Versus",
+        }
+    }
+
+    /// whether `shooter`'s weapons can hurt `victim`, everyone can hurt themselves
+    fn friendly_fire(&self, shooter: Option<PlayerId>, victim: PlayerId) -> bool {
+        match shooter {
+            Some(shooter) => shooter == victim || *self == GameMode::Versus,
+            None => true,
+        }
+    }
+}
+
+/// One panel per player holding their score, heat, lives and power-ups.
+#[derive(Component)]
+pub struct PlayerHud;
+
+#[derive(Component)]
+pub struct PlayerScore;
+
+#[derive(Component)]
+pub struct PlayerHeat;
+
+#[derive(Component)]
+pub struct PlayerLives;
+
+#[derive(Component)]
+pub struct PowerUpHud {
+    /// its sections come and go with the effects, so it keeps the font to hand
+    font: Handle<Font>,
+}
+
+// Events
+
+/// Sent whenever an enemy is destroyed, whatever destroyed it.
+pub struct EnemyDestroyed {
+    pub kind: EnemyKind,
+    pub position: Vec3,
+}
+
+/// Starts the game with the options given on the command line.
+pub fn run() {
+    let options = LaunchOptions::from_args();
+    let mut app = App::new();
+
+    if options.headless {
+        // no renderer and no window, frames run back to back as fast as they can
+        app.insert_resource(WgpuSettings {
+            backends: None,
+            ..Default::default()
+        })
+        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::ZERO))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    add_primary_window: false,
+                    exit_on_all_closed: false,
+                    ..Default::default()
+                })
+                .disable::<WinitPlugin>(),
+        )
+        .add_plugin(ScheduleRunnerPlugin);
+    } else {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
+            window: WindowDescriptor {
+                title: "Invaders must die".to_string(),
+                width: WINDOW_SIZE.0,
+                height: WINDOW_SIZE.1,
+                ..Default::default()
+            },
+            ..Default::default()
+        }));
+    }
+
+    app.insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
+        .insert_resource(options.clone())
+        .add_plugin(ControlsPlugin)
+        .add_plugin(DevicesPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(CalibrationPlugin)
+        .add_plugin(HapticsPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(BoundsPlugin)
+        .add_plugin(SpatialPlugin)
+        .add_plugin(RadarPlugin)
+        .add_plugin(FieldPlugin)
+        .add_plugin(PlayerPlugin)
+        .add_plugin(EnemyPlugin)
+        .add_plugin(MissilePlugin)
+        .add_plugin(GravityWellPlugin)
+        .add_plugin(PowerUpPlugin)
+        .add_plugin(ScorePlugin)
+        .add_plugin(SlingshotPlugin)
+        .add_plugin(OrbitPlugin)
+        .add_event::<EnemyDestroyed>()
+        .insert_resource(GameMode::default())
+        .insert_resource(Despawned::default())
+        .add_system_to_stage(CoreStage::First, despawned_clear_system)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(2.0))
+        .insert_resource(RapierConfiguration {
+            gravity: Vec2::new(0., 0.),
+            // frames in a headless run take no time at all, so the physics would barely move
+            timestep_mode: if options.headless {
+                TimestepMode::Fixed {
+                    dt: TIME_STEP,
+                    substeps: 1,
+                }
+            } else {
+                RapierConfiguration::default().timestep_mode
+            },
+            ..Default::default()
+        })
+        // times the physics step, so needs the rapier stages
+        .add_plugin(TelemetryPlugin)
+        .add_plugin(DebugPlugin)
+        .add_startup_system(setup_system)
+        .add_startup_system(setup_physics.after(setup_system))
+        .add_system(apply_gravitational_forces)
+        //.add_system(moveable_system)
+        .add_system(player_laser_hit_enemy_system)
+        .add_system(player_laser_hit_player_system)
+        .add_system(player_laser_hit_asteroid_system)
+        .add_system(explosion_to_spawn_system)
+        .add_system(area_damage_system)
+        .add_system(explosion_animation_system)
+        .add_system(enemy_laser_hit_player_system)
+        .add_system(enemy_player_collision_system)
+        .add_system(player_hud_spawn_system)
+        .add_system(player_score_update_system)
+        .add_system(player_heat_update_system)
+        .add_system(player_lives_update_system)
+        .add_system(power_up_hud_update_system);
+
+    if !options.headless {
+        app.add_plugin(RapierDebugRenderPlugin::default());
+    }
+    app.run();
+}
+
+fn setup_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut windows: ResMut<Windows>,
+) {
+    // 2d camera
+    commands
+        .spawn(Camera2dBundle::default())
+        .insert(GameCamera::default());
+
+    //capture window size, a headless run has none so lays things out for the default
+    let (win_w, win_h) = match windows.get_primary_mut() {
+        Some(window) => (window.width(), window.height()),
+        None => WINDOW_SIZE,
+    };
+
+    // position window
+    // window.set_position(IVec2::new(800, 0));
+
+    // add winsize resource
+    let win_size = WinSize { w: win_w, h: win_h };
+    commands.insert_resource(win_size);
+
+    // create explosion texture atlas
+    let texture_handle = asset_server.load(EXPLOSION);
+    let texture_atlas =
+        TextureAtlas::from_grid(texture_handle, Vec2::new(64., 64.), 4, 4, None, None);
+    let explosion = texture_atlases.add(texture_atlas);
+
+    // add game textures resource
+    let game_textures = GameTextures {
+        player: asset_server.load(PLAYER_SPRITE),
+        player_laser: asset_server.load(PLAYER_LASER_SPRITE),
+        enemy: asset_server.load(ENEMY_SPRITE),
+        explosion,
+        enemy_laser: asset_server.load(ENEMY_LASER_SPRITE),
+    };
+    commands.insert_resource(game_textures);
+    commands.insert_resource(EnemyCount(0));
+}
+
+
+fn setup_physics(
+    mut commands: Commands,
+    options: Res<LaunchOptions>,
+ ) {
+    let win_size = WinSize {
+        w: 500.,
+        h: 500.
+    };
+    
+    // FLOOR
+    /*
+    commands.spawn(Collider::cuboid(500., 10.))
+        .insert(TransformBundle::from(Transform::from_xyz(0.0, -300., 0.0)));
+        */
+    
+    // LARGE DENSE BALL
+    let mut spawn_ball = |x: f32, y: f32, r:f32, d:f32| {
+        commands.spawn(RigidBody::Dynamic)
+        .insert(Asteroid)
+        .insert(AsteroidHitScored::default())
+        .insert(Collider::ball(r))
+        .insert(TransformBundle::from(Transform::from_xyz(x, y, 0.)))
+        .insert(Velocity {
+            x: 0.,
+            y: -3.0,
+        })
+        // rapier's, so the velocity is kept when switching `OrbitIntegrator`
+        .insert(bevy_rapier2d::prelude::Velocity::zero())
+        .insert(Sleeping::disabled())
+        .insert(Ccd::enabled())
+        .insert(Restitution::coefficient(0.8))
+        .insert(ExternalForce {
+            force: Vec2::new(0., -100.),
+            torque: 0.0,
+        })
+        .insert(ExternalImpulse {
+            impulse: Vec2::new(0.0, 0.0),
+            torque_impulse: 0.,
+        })
+        .insert(ColliderMassProperties::Density(1.0))
+        .insert(ReadMassProperties(MassProperties {
+            ..Default::default()
+        }));
+    };
+
+    // create rng
+    let mut rng = rand::thread_rng();
+ 
+    // spawn n circles, spread wider when there are more than usual
+    let spread = (options.asteroids as f32 / ASTEROID_COUNT as f32).sqrt().max(1.);
+    for _ in 0..options.asteroids {
+        let x: f32 = rng.gen_range(-win_size.w * spread..win_size.w * spread);
+        let y: f32 = rng.gen_range(-win_size.h * spread..win_size.h * spread);
+        let r: f32 = rng.gen_range(5.0..150.0);
+        let d: f32 = rng.gen_range(0.4..3.0);
+        spawn_ball(x, y, r, d);
+    }
+
+
+    // cuboid
+    let mut spawn_cuboid = |x, y| {
+        commands.spawn(RigidBody::Dynamic)
+            .insert(Collider::cuboid(10., 20.))
+            .insert(TransformBundle::from(Transform::from_xyz(x, y, 0.,)))
+            .insert(ExternalForce {
+                force: Vec2::new(0., -100.),
+                torque: 0.
+            })
+            .insert(Restitution::coefficient(0.8))
+            .insert(ColliderMassProperties::Density(1.0))
+            .insert(ExternalImpulse {
+                impulse: Vec2::new(0.0, 0.0),
+                torque_impulse: 0.,
+            })
+            .insert(ReadMassProperties(MassProperties {
+                ..Default::default()
+            }));
+    };
+    
+
+    for n in 0..4 {
+        let x: f32 = rng.gen_range(-win_size.w..win_size.w);
+        let y: f32 = rng.gen_range(-win_size.h..win_size.h);
+        spawn_cuboid(x, y);
+    }
+
+
+    let mut spawn_triangle = |x, y| {
+        commands.spawn(RigidBody::Dynamic)
+            .insert(Collider::triangle(Vec2::new(20., 20.), Vec2::new(20., 20.), Vec2::new(20., 20.)))
+            .insert(TransformBundle::from(Transform::from_xyz(x, y, 0.,)))
+            .insert(ExternalForce {
+                force: Vec2::new(0., -100.),
+                torque: 0.
+            })
+            .insert(Restitution::coefficient(0.8))
+            .insert(ColliderMassProperties::Density(1.0))
+            .insert(ExternalImpulse {
+                impulse: Vec2::new(0.0, 0.0),
+                torque_impulse: 0.,
+            })
+            .insert(ReadMassProperties(MassProperties {
+                ..Default::default()
+            }));
+    };
+
+    
+        
+    
+    
+        
+        
+  
+}
+
+
+
+
+
+// writes each body's gravity into its ExternalForce, thrusters add to it afterwards
+pub fn apply_gravitational_forces(
+    world_bounds: Res<WorldBounds>,
+    query: Query<(Entity, &Transform, &ReadMassProperties)>,
+    source_query: Query<(&Transform, &GravitySource)>,
+    mut force_query: Query<&mut ExternalForce>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Gravity);
+    // rapier keeps bodies from overlapping, so they are pulled on as points
+    let bodies: Vec<(Entity, Vec2, f32, f32)> = query
+        .iter()
+        .map(|(ent, tf, mass_prop)| (ent, tf.translation.xy(), mass_prop.0.mass, 0.))
+        .collect();
+    let sources: Vec<(Vec2, f32)> = source_query
+        .iter()
+        .map(|(source_tf, source)| (source_tf.translation.xy(), source.mass))
+        .collect();
+
+    let forces = gravity_forces(&world_bounds, &bodies, &sources);
+    for ((ent, ..), force) in bodies.iter().zip(forces) {
+        if let Ok(mut ext_force) = force_query.get_mut(*ent) {
+            ext_force.force = force;
+            // gravity never turns anything, so a turn left over from the controls is cleared
+            ext_force.torque = 0.;
+        }
+    }
+}
+
+/// Gravity on each of `bodies`, given as (entity, position, mass, radius), from all the
+/// others and from the gravity sources, in the same order, worked out across the compute
+/// task pool. Two bodies closer than their radii add up to pull as if just touching.
+fn gravity_forces(
+    world_bounds: &WorldBounds,
+    bodies: &[(Entity, Vec2, f32, f32)],
+    sources: &[(Vec2, f32)],
+) -> Vec<Vec2> {
+    // every task adds up forces in a buffer of its own, summed once they have all finished
+    let pool = ComputeTaskPool::get();
+    let tasks = (bodies.len() / GRAVITY_BODIES_PER_TASK).clamp(1, pool.thread_num().max(1));
+    if tasks == 1 {
+        return gravity_rows(world_bounds, bodies, sources, 0, 1);
+    }
+    let buffers = pool.scope(|scope| {
+        for task in 0..tasks {
+            scope.spawn(async move { gravity_rows(world_bounds, bodies, sources, task, tasks) });
+        }
+    });
+    (0..bodies.len())
+        .map(|n| buffers.iter().map(|forces| forces[n]).sum())
+        .collect()
+}
+
+/// Forces from every pair whose first body is in rows `task`, `task + tasks` and so on,
+/// plus the pull of the gravity sources on those bodies. Later rows have fewer pairs left,
+/// so dealing the rows out in turn keeps the tasks even.
+fn gravity_rows(
+    world_bounds: &WorldBounds,
+    bodies: &[(Entity, Vec2, f32, f32)],
+    sources: &[(Vec2, f32)],
+    task: usize,
+    tasks: usize,
+) -> Vec<Vec2> {
+    let mut forces = vec![Vec2::ZERO; bodies.len()];
+
+    for n_1 in (task..bodies.len()).step_by(tasks) {
+        let (_, position_1, mass_1, radius_1) = bodies[n_1];
+        // ignore first n terms as have already been calcualted
+        for (n_2, (_, position_2, mass_2, radius_2)) in bodies.iter().enumerate().skip(n_1 + 1) {
+            // change in (x, y) between two points, across the seam if that is closer
+            let offset = world_bounds.offset(*position_2, position_1);
+            // bodies right on top of each other have no direction to pull in
+            if offset.length_squared() == 0. {
+                continue;
+            }
+            // bodies passing through each other would otherwise be flung apart
+            let distance_squared = offset.length_squared().max((radius_1 + radius_2).powi(2));
+            // Newton's law of gravitation * extra strong gravity
+            let force = (mass_1 * mass_2 * G) / distance_squared * EXTRA_GRAVITY;
+            // pulls entity_1 towards entity_2, and the equal opposite force back
+            let force = offset.normalize() * force;
+            forces[n_1] -= force;
+            forces[n_2] += force;
+        }
+
+        // gravity sources pull on every body but are not pulled back
+        for (source_position, source_mass) in sources {
+            let offset = world_bounds.offset(position_1, *source_position);
+            // soften the pull near the centre so bodies are not flung out at infinite force
+            let distance_squared =
+                offset.length_squared().max(GRAVITY_SOURCE_MIN_DISTANCE.powi(2));
+            let force = (mass_1 * source_mass * G) / distance_squared * EXTRA_GRAVITY;
+            forces[n_1] += offset.normalize_or_zero() * force;
+        }
+    }
+    forces
+}
+
+
+
+
+
+
+/// What a player shooting down an enemy changes.
+#[derive(SystemParam)]
+pub struct EnemyKills<'w, 's> {
+    enemy_count: ResMut<'w, EnemyCount>,
+    despawned: ResMut<'w, Despawned>,
+    enemy_destroyed: EventWriter<'w, 's, EnemyDestroyed>,
+    score_events: EventWriter<'w, 's, ScoreEvent>,
+}
+
+type PlayerLasers<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static SpriteSize,
+        &'static FromPlayer,
+        Option<&'static GravityAssist>,
+    ),
+    With<Laser>,
+>;
+
+pub fn player_laser_hit_enemy_system(
+    mut commands: Commands,
+    kills: EnemyKills,
+    spatial_index: Res<SpatialIndex>,
+    laser_query: PlayerLasers,
+    enemy_query: Query<(&Transform, &EnemyKind), With<Enemy>>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Collision);
+    let EnemyKills {
+        mut enemy_count,
+        mut despawned,
+        mut enemy_destroyed,
+        mut score_events,
+    } = kills;
+
+    // iterate through the lasers
+    for (laser_entity, laser_tf, laser_size, from_player, gravity_assist) in laser_query.iter() {
+        // only the enemies near enough to overlap the laser
+        let hit = spatial_index
+            .overlapping(SpatialLayer::Enemy, sprite_rect(laser_tf, laser_size))
+            .filter_map(|enemy| Some((enemy.entity, enemy_query.get(enemy.entity).ok()?)))
+            .find(|(enemy_entity, _)| !despawned.0.contains(enemy_entity));
+        let (enemy_entity, (enemy_tf, enemy_kind)) = match hit {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        // perform collision logic
+        commands.entity(enemy_entity).despawn();
+        despawned.0.insert(enemy_entity);
+        enemy_count.0 -= 1;
+
+        commands.entity(laser_entity).despawn();
+
+        // add to score
+        score_events.send(ScoreEvent {
+            player: from_player.0,
+            reason: ScoreReason::Kill(*enemy_kind),
+            position: enemy_tf.translation,
+        });
+
+        // bonus when gravity bent the laser onto its target
+        if let Some(gravity_assist) = gravity_assist {
+            if gravity_assist.bend >= CURVED_SHOT_MIN_ANGLE {
+                score_events.send(ScoreEvent {
+                    player: from_player.0,
+                    reason: ScoreReason::TrickShot,
+                    position: enemy_tf.translation,
+                });
+            }
+        }
+
+        // spawn explosionToSpawn
+        commands.spawn(ExplosionToSpawn(enemy_tf.translation));
+
+        enemy_destroyed.send(EnemyDestroyed {
+            kind: *enemy_kind,
+            position: enemy_tf.translation,
+        });
+    }
+}
+
+/// Player lasers break up on asteroids. A hit scores a little, but each asteroid only
+/// once every `ASTEROID_HIT_INTERVAL`, however fast it is shot.
+fn player_laser_hit_asteroid_system(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut score_events: EventWriter<ScoreEvent>,
+    time: Res<Time>,
+    laser_query: Query<(&Transform, &FromPlayer), With<Laser>>,
+    mut asteroid_query: Query<&mut AsteroidHitScored, With<Asteroid>>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Collision);
+    let now = time.elapsed_seconds_f64();
+    let mut despawned_lasers: HashSet<Entity> = HashSet::new();
+
+    for event in collision_events.iter() {
+        let (entity_1, entity_2) = match event {
+            CollisionEvent::Started(entity_1, entity_2, _) => (*entity_1, *entity_2),
+            CollisionEvent::Stopped(..) => continue,
+        };
+
+        let (laser_entity, asteroid_entity) =
+            if laser_query.contains(entity_1) && asteroid_query.contains(entity_2) {
+                (entity_1, entity_2)
+            } else if laser_query.contains(entity_2) && asteroid_query.contains(entity_1) {
+                (entity_2, entity_1)
+            } else {
+                continue;
+            };
+        if !despawned_lasers.insert(laser_entity) {
+            continue;
+        }
+
+        let (laser_tf, from_player) = laser_query.get(laser_entity).unwrap();
+        commands.entity(laser_entity).despawn();
+
+        let mut hit_scored = asteroid_query.get_mut(asteroid_entity).unwrap();
+        if hit_scored
+            .0
+            .is_some_and(|last| now < last + ASTEROID_HIT_INTERVAL)
+        {
+            continue;
+        }
+        hit_scored.0 = Some(now);
+        score_events.send(ScoreEvent {
+            player: from_player.0,
+            reason: ScoreReason::AsteroidHit,
+            position: laser_tf.translation,
+        });
+    }
+}
+
+/// What a ship going down changes, and the time it went down for its respawn delay.
+#[derive(SystemParam)]
+struct ShipLosses<'w, 's> {
+    players: ResMut<'w, Players>,
+    despawned: ResMut<'w, Despawned>,
+    time: Res<'w, Time>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// The ships, and the spatial index to find what they overlap.
+#[derive(SystemParam)]
+struct Ships<'w, 's> {
+    spatial_index: Res<'w, SpatialIndex>,
+    query: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static PlayerId,
+            &'static Transform,
+            &'static SpriteSize,
+            &'static ActivePowerUps,
+        ),
+        With<Player>,
+    >,
+}
+
+fn enemy_laser_hit_player_system(
+    mut commands: Commands,
+    losses: ShipLosses,
+    ships: Ships,
+    laser_query: Query<(), (With<Laser>, With<FromEnemy>)>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Collision);
+    let ShipLosses {
+        mut players,
+        mut despawned,
+        time,
+        ..
+    } = losses;
+    let Ships {
+        spatial_index,
+        query: player_query,
+    } = ships;
+    let mut despawned_lasers: HashSet<Entity> = HashSet::new();
+
+    for (player_entity, player_id, player_tf, player_size, power_ups) in player_query.iter() {
+        // already shot down by something else this frame
+        if despawned.0.contains(&player_entity) {
+            continue;
+        }
+        let lasers = spatial_index
+            .overlapping(SpatialLayer::EnemyLaser, sprite_rect(player_tf, player_size))
+            .map(|laser| laser.entity)
+            .filter(|laser_entity| laser_query.contains(*laser_entity));
+
+        for laser_entity in lasers {
+            if despawned_lasers.contains(&laser_entity) {
+                continue;
+            }
+
+            //perform the collision
+            commands.entity(laser_entity).despawn();
+            despawned_lasers.insert(laser_entity);
+
+            // the shield soaks up the laser
+            if power_ups.is_active(PowerUpKind::Shield) {
+                continue;
+            }
+
+            // despawn player
+            commands.entity(player_entity).despawn();
+            despawned.0.insert(player_entity);
+            players.state(*player_id).shot(time.elapsed_seconds_f64());
+
+            // spawn explosion
+            commands.spawn(ExplosionToSpawn(player_tf.translation));
+
+            break;
+        }
+    }
+}
+
+/// In versus, player lasers shoot down other players and score for the shooter.
+fn player_laser_hit_player_system(
+    mut commands: Commands,
+    losses: ShipLosses,
+    mut score_events: EventWriter<ScoreEvent>,
+    game_mode: Res<GameMode>,
+    ships: Ships,
+    laser_query: Query<&FromPlayer, With<Laser>>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Collision);
+    let ShipLosses {
+        mut players,
+        mut despawned,
+        time,
+        ..
+    } = losses;
+    let Ships {
+        spatial_index,
+        query: player_query,
+    } = ships;
+    let mut despawned_lasers: HashSet<Entity> = HashSet::new();
+
+    for (player_entity, player_id, player_tf, player_size, power_ups) in player_query.iter() {
+        if despawned.0.contains(&player_entity) {
+            continue;
+        }
+        let lasers = spatial_index
+            .overlapping(SpatialLayer::PlayerLaser, sprite_rect(player_tf, player_size))
+            .filter_map(|laser| Some((laser.entity, laser_query.get(laser.entity).ok()?)));
+
+        for (laser_entity, from_player) in lasers {
+            // lasers leave their own ship from inside its outline
+            if from_player.0 == *player_id
+                || !game_mode.friendly_fire(Some(from_player.0), *player_id)
+                || despawned_lasers.contains(&laser_entity)
+            {
+                continue;
+            }
+
+            commands.entity(laser_entity).despawn();
+            despawned_lasers.insert(laser_entity);
+
+            if power_ups.is_active(PowerUpKind::Shield) {
+                continue;
+            }
+
+            commands.entity(player_entity).despawn();
+            despawned.0.insert(player_entity);
+            players.state(*player_id).shot(time.elapsed_seconds_f64());
+            commands.spawn(ExplosionToSpawn(player_tf.translation));
+            score_events.send(ScoreEvent {
+                player: from_player.0,
+                reason: ScoreReason::PlayerKill,
+                position: player_tf.translation,
+            });
+
+            break;
+        }
+    }
+}
+
+fn explosion_to_spawn_system(
+    mut commands: Commands,
+    game_textures: Res<GameTextures>,
+    query: Query<(Entity, &ExplosionToSpawn)>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Spawn);
+    for (explosion_spawn_entity, explosion_to_spawn) in query.iter() {
+        //spawn explosion sprite
+        commands
+            .spawn(SpriteSheetBundle {
+                texture_atlas: game_textures.explosion.clone(),
+                transform: Transform {
+                    translation: explosion_to_spawn.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(Explosion)
+            .insert(ExplosionTimer::default());
+
+        // despawn the explosionToSpawn
+        commands.entity(explosion_spawn_entity).despawn();
+    }
+}
+
+/// What a blast changes when it takes out an enemy or a ship.
+#[derive(SystemParam)]
+struct Casualties<'w, 's> {
+    enemy_count: ResMut<'w, EnemyCount>,
+    despawned: ResMut<'w, Despawned>,
+    players: ResMut<'w, Players>,
+    enemy_destroyed: EventWriter<'w, 's, EnemyDestroyed>,
+    score_events: EventWriter<'w, 's, ScoreEvent>,
+}
+
+type NotShipOrEnemy = (Without<Enemy>, Without<Player>);
+
+/// Everything a blast can catch, found through the spatial index.
+#[derive(SystemParam)]
+struct BlastTargets<'w, 's> {
+    world_bounds: Res<'w, WorldBounds>,
+    spatial_index: Res<'w, SpatialIndex>,
+    enemy_query: Query<'w, 's, (&'static Transform, &'static EnemyKind), With<Enemy>>,
+    player_query: Query<
+        'w,
+        's,
+        (&'static PlayerId, &'static Transform, &'static ActivePowerUps),
+        With<Player>,
+    >,
+    body_query: Query<'w, 's, (&'static Transform, &'static mut ExternalImpulse), NotShipOrEnemy>,
+}
+
+fn area_damage_system(
+    mut commands: Commands,
+    casualties: Casualties,
+    game_mode: Res<GameMode>,
+    time: Res<Time>,
+    blast_query: Query<(&ExplosionToSpawn, &AreaDamage)>,
+    targets: BlastTargets,
+) {
+    let Casualties {
+        mut enemy_count,
+        mut despawned,
+        mut players,
+        mut enemy_destroyed,
+        mut score_events,
+    } = casualties;
+    let BlastTargets {
+        world_bounds,
+        spatial_index,
+        enemy_query,
+        player_query,
+        mut body_query,
+    } = targets;
+
+    for (explosion, area_damage) in blast_query.iter() {
+        let centre = explosion.0.xy();
+        let caught = |layer| {
+            spatial_index
+                .within_radius(layer, centre, area_damage.radius)
+                .map(|entry| entry.entity)
+        };
+
+        for enemy_entity in caught(SpatialLayer::Enemy) {
+            let (enemy_tf, enemy_kind) = match enemy_query.get(enemy_entity) {
+                Ok(enemy) if !despawned.0.contains(&enemy_entity) => enemy,
+                _ => continue,
+            };
+            commands.entity(enemy_entity).despawn();
+            despawned.0.insert(enemy_entity);
+            enemy_count.0 -= 1;
+            if let Some(owner) = area_damage.owner {
+                score_events.send(ScoreEvent {
+                    player: owner,
+                    reason: ScoreReason::Kill(*enemy_kind),
+                    position: enemy_tf.translation,
+                });
+            }
+            commands.spawn(ExplosionToSpawn(enemy_tf.translation));
+            enemy_destroyed.send(EnemyDestroyed {
+                kind: *enemy_kind,
+                position: enemy_tf.translation,
+            });
+        }
+
+        // players are not safe from their own blast, or anyone's in versus
+        for player_entity in caught(SpatialLayer::Player) {
+            let (player_id, player_tf, power_ups) = match player_query.get(player_entity) {
+                Ok(player) if !despawned.0.contains(&player_entity) => player,
+                _ => continue,
+            };
+            if power_ups.is_active(PowerUpKind::Shield)
+                || !game_mode.friendly_fire(area_damage.owner, *player_id)
+            {
+                continue;
+            }
+            commands.entity(player_entity).despawn();
+            despawned.0.insert(player_entity);
+            players.state(*player_id).shot(time.elapsed_seconds_f64());
+            commands.spawn(ExplosionToSpawn(player_tf.translation));
+
+            if let Some(owner) = area_damage.owner.filter(|owner| owner != player_id) {
+                score_events.send(ScoreEvent {
+                    player: owner,
+                    reason: ScoreReason::PlayerKill,
+                    position: player_tf.translation,
+                });
+            }
+        }
+
+        // everything else gets knocked back, falling off towards the edge of the blast
+        for body_entity in caught(SpatialLayer::Body) {
+            let (body_tf, mut ext_impulse) = match body_query.get_mut(body_entity) {
+                Ok(body) => body,
+                Err(_) => continue,
+            };
+            let offset = world_bounds.offset(centre, body_tf.translation.xy());
+            let distance = offset.length();
+            // the index is from the start of the frame, the body may have moved since
+            if distance > area_damage.radius || distance == 0. {
+                continue;
+            }
+            let falloff = 1. - distance / area_damage.radius;
+            ext_impulse.impulse += offset / distance * BLAST_IMPULSE * falloff;
+        }
+    }
+}
+
+fn despawned_clear_system(mut despawned: ResMut<Despawned>) {
+    despawned.0.clear();
+}
+
+fn explosion_animation_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut ExplosionTimer, &mut TextureAtlasSprite), With<Explosion>>,
+) {
+    for (entity, mut timer, mut sprite) in query.iter_mut() {
+        timer.0.tick(time.delta());
+        if timer.0.finished() {
+            sprite.index += 1; // move to next sprite cell
+            if sprite.index >= EXPLOSION_LENGTH {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+fn enemy_player_collision_system(
+    mut commands: Commands,
+    losses: ShipLosses,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut enemy_destroyed: EventWriter<EnemyDestroyed>,
+    ships: Ships,
+    enemy_query: Query<(&Transform, &EnemyKind), With<Enemy>>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Collision);
+    let ShipLosses {
+        mut players,
+        mut despawned,
+        time,
+        ..
+    } = losses;
+    let Ships {
+        spatial_index,
+        query: player_query,
+    } = ships;
+
+    for (player_entity, player_id, player_tf, player_size, power_ups) in player_query.iter() {
+        if despawned.0.contains(&player_entity) {
+            continue;
+        }
+        let enemies = spatial_index
+            .overlapping(SpatialLayer::Enemy, sprite_rect(player_tf, player_size))
+            .filter_map(|enemy| Some((enemy.entity, enemy_query.get(enemy.entity).ok()?)));
+
+        for (enemy_entity, (enemy_tf, enemy_kind)) in enemies {
+            if despawned.0.contains(&enemy_entity) {
+                continue;
+            }
+
+            //perform the collision
+            commands.entity(enemy_entity).despawn();
+            despawned.0.insert(enemy_entity);
+            commands.spawn(ExplosionToSpawn(enemy_tf.translation));
+            enemy_count.0 -= 1;
+            enemy_destroyed.send(EnemyDestroyed {
+                kind: *enemy_kind,
+                position: enemy_tf.translation,
+            });
+
+            // a shielded player rams straight through
+            if power_ups.is_active(PowerUpKind::Shield) {
+                continue;
+            }
+
+            // despawn player
+            commands.entity(player_entity).despawn();
+            despawned.0.insert(player_entity);
+            players.state(*player_id).shot(time.elapsed_seconds_f64());
+            commands.spawn(ExplosionToSpawn(player_tf.translation));
+
+            break;
+        }
+    }
+}
+
+/// Gives each player that has joined a HUD panel, P1 to P4 clockwise from the top left,
+/// and takes it away again when they leave.
+fn player_hud_spawn_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    players: Res<Players>,
+    hud_query: Query<(Entity, &PlayerId), With<PlayerHud>>,
+) {
+    for (hud_entity, id) in hud_query.iter() {
+        if !players.0.contains_key(id) {
+            commands.entity(hud_entity).despawn_recursive();
+        }
+    }
+
+    for id in players.0.keys() {
+        if hud_query.iter().any(|(_, hud_id)| hud_id == id) {
+            continue;
+        }
+
+        let font = asset_server.load("fonts/FiraSans-Black.ttf");
+        let label_style = |font_size| TextStyle {
+            font: font.clone(),
+            font_size,
+            color: id.color(),
+        };
+        let value_style = |font_size, color| TextStyle {
+            font: font.clone(),
+            font_size,
+            color,
+        };
+
+        let corner = match id.0 {
+            0 => UiRect {
+                top: Val::Px(0.0),
+                left: Val::Px(0.0),
+                ..Default::default()
+            },
+            1 => UiRect {
+                top: Val::Px(0.0),
+                right: Val::Px(10.0),
+                ..Default::default()
+            },
+            2 => UiRect {
+                bottom: Val::Px(0.0),
+                right: Val::Px(10.0),
+                ..Default::default()
+            },
+            _ => UiRect {
+                bottom: Val::Px(0.0),
+                left: Val::Px(0.0),
+                ..Default::default()
+            },
+        };
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: corner,
+                        flex_direction: FlexDirection::Column,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                PlayerHud,
+                *id,
+            ))
+            .with_children(|panel| {
+                panel.spawn((
+                    TextBundle::from_sections([
+                        TextSection::new(format!("{} Score: ", id.label()), label_style(60.0)),
+                        TextSection::from_style(value_style(60.0, Color::GOLD)),
+                        // combo multiplier, only shown while above x1
+                        TextSection::from_style(value_style(40.0, Color::ORANGE_RED)),
+                    ]),
+                    PlayerScore,
+                    *id,
+                ));
+
+                // weapon heat meter below the score
+                panel.spawn((
+                    TextBundle::from_sections([
+                        TextSection::new("Heat: ", label_style(30.0)),
+                        TextSection::from_style(value_style(30.0, Color::ORANGE)),
+                    ]),
+                    PlayerHeat,
+                    *id,
+                ));
+
+                panel.spawn((
+                    TextBundle::from_sections([
+                        TextSection::new("Lives: ", label_style(30.0)),
+                        TextSection::from_style(value_style(30.0, Color::GREEN)),
+                    ]),
+                    PlayerLives,
+                    *id,
+                ));
+
+                // active power-ups, one section per effect, filled in by power_up_hud_update_system
+                panel.spawn((TextBundle::default(), PowerUpHud { font: font.clone() }, *id));
+            });
+    }
+}
+
+fn player_score_update_system(
+    players: Res<Players>,
+    combos: Res<Combos>,
+    mut query: Query<(&PlayerId, &mut Text), With<PlayerScore>>,
+) {
+    for (id, mut text) in &mut query {
+        let score = players.0.get(id).map_or(0, |player_state| player_state.score);
+        let multiplier = combos.multiplier(*id);
+        text.sections[1].value = score.to_string();
+        text.sections[2].value = if multiplier > 1. {
+            format!(" x{:.2}", multiplier)
+        } else {
+            String::new()
+        };
+    }
+}
+
+fn player_heat_update_system(
+    heat_query: Query<(&PlayerId, &WeaponHeat), With<Player>>,
+    mut query: Query<(&PlayerId, &mut Text), With<PlayerHeat>>,
+) {
+    for (id, mut text) in &mut query {
+        let (heat, overheated) = match heat_query.iter().find(|(ship_id, _)| *ship_id == id) {
+            Some((_, weapon_heat)) => (weapon_heat.heat, weapon_heat.overheated),
+            None => (0., false),
+        };
+
+        text.sections[1].value = if overheated {
+            "OVERHEATED".to_string()
+        } else {
+            format!("{:.0}%", heat * 100.)
+        };
+        text.sections[1].style.color = if overheated {
+            Color::RED
+        } else {
+            Color::ORANGE
+        };
+    }
+}
+
+fn player_lives_update_system(
+    players: Res<Players>,
+    mut query: Query<(&PlayerId, &mut Text), With<PlayerLives>>,
+) {
+    for (id, mut text) in &mut query {
+        let lives = players.0.get(id).map_or(0, |player_state| player_state.lives);
+        text.sections[1].value = lives.to_string();
+    }
+}
+
+fn power_up_hud_update_system(
+    power_up_query: Query<(&PlayerId, &ActivePowerUps), With<Player>>,
+    mut query: Query<(&PlayerId, &PowerUpHud, &mut Text)>,
+) {
+    for (id, hud, mut text) in &mut query {
+        let mut active: Vec<(PowerUpKind, f32)> =
+            match power_up_query.iter().find(|(ship_id, _)| *ship_id == id) {
+                Some((_, power_ups)) => power_ups
+                    .timers
+                    .iter()
+                    .map(|(kind, timer)| (*kind, timer.remaining_secs()))
+                    .collect(),
+                None => Vec::new(),
+            };
+        // longest running effect first
+        active.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let shown: Vec<(String, Color)> = active
+            .iter()
+            .map(|(kind, remaining)| {
+                let value = format!("{} {:.0}s\n", kind.label(), remaining.ceil());
+                (value, kind.color())
+            })
+            .collect();
+        // the countdown only moves once a second, the text is left alone in between
+        let unchanged = text.sections.len() == shown.len()
+            && text
+                .sections
+                .iter()
+                .zip(&shown)
+                .all(|(section, (value, color))| {
+                    section.value == *value && section.style.color == *color
+                });
+        if unchanged {
+            continue;
+        }
+
+        text.sections = shown
+            .into_iter()
+            .map(|(value, color)| {
+                TextSection::new(
+                    value,
+                    TextStyle {
+                        font: hud.font.clone(),
+                        font_size: 24.0,
+                        color,
+                    },
+                )
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(mode: BoundaryMode) -> WorldBounds {
+        WorldBounds {
+            size: Vec2::new(1000., 800.),
+            mode,
+        }
+    }
+
+    #[test]
+    fn wrap_brings_positions_back_inside() {
+        let world = bounds(BoundaryMode::Wrap);
+        assert_eq!(world.wrap(Vec2::new(100., -50.)), Vec2::new(100., -50.));
+        assert_eq!(world.wrap(Vec2::new(510., 0.)), Vec2::new(-490., 0.));
+        assert_eq!(world.wrap(Vec2::new(-510., -410.)), Vec2::new(490., 390.));
+        // however many times round
+        assert_eq!(world.wrap(Vec2::new(2600., 1700.)), Vec2::new(-400., 100.));
+    }
+
+    #[test]
+    fn offset_takes_the_short_way_across_the_seams() {
+        let world = bounds(BoundaryMode::Wrap);
+        let offset = world.offset(Vec2::new(490., 390.), Vec2::new(-490., -390.));
+        assert_eq!(offset, Vec2::new(20., 20.));
+        let offset = world.offset(Vec2::new(-100., 0.), Vec2::new(100., 0.));
+        assert_eq!(offset, Vec2::new(200., 0.));
+    }
+
+    #[test]
+    fn offset_goes_straight_across_without_wrapping() {
+        for mode in [BoundaryMode::Soft, BoundaryMode::Walls] {
+            let offset = bounds(mode).offset(Vec2::new(490., 390.), Vec2::new(-490., -390.));
+            assert_eq!(offset, Vec2::new(-980., -780.));
+        }
+    }
+
+    #[test]
+    fn gravity_split_over_tasks_sums_to_a_single_pass() {
+        let world = bounds(BoundaryMode::Wrap);
+        // bodies and sources on both sides of the seams, so some pairs pull across them
+        let bodies: Vec<(Entity, Vec2, f32, f32)> = [
+            (480., 380.),
+            (-485., -370.),
+            (-490., 100.),
+            (470., -390.),
+            (0., 0.),
+            (120., -60.),
+            (-300., 250.),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(n, (x, y))| (Entity::from_raw(n as u32), Vec2::new(x, y), 10. + n as f32, 5.))
+        .collect();
+        let sources = [(Vec2::new(495., 0.), 500.), (Vec2::new(-200., -395.), 300.)];
+
+        let single = gravity_rows(&world, &bodies, &sources, 0, 1);
+        for tasks in 2..=4 {
+            let split = (0..tasks)
+                .map(|task| gravity_rows(&world, &bodies, &sources, task, tasks))
+                .fold(vec![Vec2::ZERO; bodies.len()], |sum, forces| {
+                    sum.iter().zip(forces).map(|(a, b)| *a + b).collect()
+                });
+            for (a, b) in single.iter().zip(&split) {
+                assert!((*a - *b).length() < 1e-3 * a.length().max(1.), "{a} != {b}");
+            }
+        }
+    }
+}
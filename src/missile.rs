@@ -0,0 +1,215 @@
+use crate::{
+    apply_gravitational_forces,
+    components::{
+        AreaDamage, DespawnOutOfBounds, Enemy, ExplosionToSpawn, FromPlayer, Missile,
+        MissileLauncher, Orientation, Player, PlayerId,
+    },
+    controls::{Action, ActionState},
+    haptics::RumbleEvent,
+    spatial::{SpatialIndex, SpatialLayer},
+    GameTextures, WorldBounds, MISSILE_BLAST_RADIUS, MISSILE_COOLDOWN, MISSILE_FUEL,
+    MISSILE_PROXIMITY_FUSE, MISSILE_SEEK_CONE, MISSILE_SEEK_RANGE, MISSILE_THRUST,
+    MISSILE_TURN_RATE, RUMBLE_MISSILE, SPRITE_SCALE,
+};
+use bevy::{math::Vec3Swizzles, prelude::*, utils::HashSet};
+use bevy_rapier2d::{
+    prelude::{
+        ActiveEvents, Collider, CollisionEvent, ExternalForce, MassProperties, ReadMassProperties,
+        RigidBody, Velocity,
+    },
+    rapier::geometry::CollisionEventFlags,
+};
+
+pub struct MissilePlugin;
+
+impl Plugin for MissilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(missile_fire_system)
+            .add_system(missile_targeting_system)
+            .add_system(
+                missile_steering_system
+                    .after(missile_targeting_system)
+                    .after(apply_gravitational_forces),
+            )
+            .add_system(missile_detonation_system);
+    }
+}
+
+fn missile_fire_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    game_textures: Res<GameTextures>,
+    mut rumble: EventWriter<RumbleEvent>,
+    mut query: Query<(&PlayerId, &Transform, &Orientation, &mut MissileLauncher), With<Player>>,
+) {
+    let now = time.elapsed_seconds_f64();
+
+    for (id, player_tf, orientation, mut launcher) in query.iter_mut() {
+        if !actions.player(*id).pressed(Action::Missile) {
+            continue;
+        }
+        if launcher.last_fired != -1. && now < launcher.last_fired + MISSILE_COOLDOWN {
+            continue;
+        }
+        launcher.last_fired = now;
+        let (strength, duration) = RUMBLE_MISSILE;
+        rumble.send(RumbleEvent {
+            player: *id,
+            strength,
+            duration,
+        });
+
+        // launch from just ahead of the nose
+        let theta = orientation.theta;
+        let heading = Vec2::new(-theta.sin(), theta.cos());
+        let position = player_tf.translation.xy() + heading * 50.;
+
+        commands
+            .spawn(SpriteBundle {
+                texture: game_textures.player_laser.clone(),
+                sprite: Sprite {
+                    color: Color::ORANGE_RED,
+                    ..Default::default()
+                },
+                transform: Transform {
+                    translation: position.extend(0.),
+                    rotation: Quat::from_rotation_z(theta),
+                    scale: Vec3::new(SPRITE_SCALE * 1.5, SPRITE_SCALE, 1.),
+                },
+                ..Default::default()
+            })
+            .insert(Missile {
+                target: None,
+                fuel: Timer::from_seconds(MISSILE_FUEL, TimerMode::Once),
+            })
+            .insert(FromPlayer(*id))
+            .insert(RigidBody::Dynamic)
+            .insert(Collider::cuboid(2., 6.))
+            .insert(ActiveEvents::COLLISION_EVENTS)
+            .insert(Velocity::default())
+            .insert(ExternalForce::default())
+            .insert(ReadMassProperties(MassProperties {
+                ..Default::default()
+            }))
+            .insert(DespawnOutOfBounds);
+    }
+}
+
+/// Keeps the current target while it exists, otherwise locks on to the
+/// nearest enemy inside the seeker cone.
+fn missile_targeting_system(
+    world_bounds: Res<WorldBounds>,
+    spatial_index: Res<SpatialIndex>,
+    mut missile_query: Query<(&Transform, &mut Missile)>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    for (missile_tf, mut missile) in missile_query.iter_mut() {
+        if let Some(target) = missile.target {
+            if enemy_query.get(target).is_ok() {
+                continue;
+            }
+        }
+
+        let position = missile_tf.translation.xy();
+        let heading = (missile_tf.rotation * Vec3::Y).xy();
+
+        missile.target = spatial_index
+            .nearest(SpatialLayer::Enemy, position, MISSILE_SEEK_RANGE, |enemy| {
+                let offset = world_bounds.offset(position, enemy.position);
+                heading.angle_between(offset).abs() <= MISSILE_SEEK_CONE
+                    && enemy_query.contains(enemy.entity)
+            })
+            .map(|enemy| enemy.entity);
+    }
+}
+
+/// Turns towards the target no faster than the turn rate and adds the thrust
+/// on top of the gravity already written into `ExternalForce`.
+fn missile_steering_system(
+    time: Res<Time>,
+    world_bounds: Res<WorldBounds>,
+    mut missile_query: Query<(&Transform, &Missile, &mut Velocity, &mut ExternalForce)>,
+    enemy_query: Query<&Transform, With<Enemy>>,
+) {
+    for (missile_tf, missile, mut velocity, mut ext_force) in missile_query.iter_mut() {
+        let heading = (missile_tf.rotation * Vec3::Y).xy();
+
+        velocity.angvel = match missile
+            .target
+            .and_then(|target| enemy_query.get(target).ok())
+        {
+            Some(target_tf) => {
+                let offset =
+                    world_bounds.offset(missile_tf.translation.xy(), target_tf.translation.xy());
+                let error = heading.angle_between(offset);
+                // turn at full rate unless the target is reachable within this frame
+                let max_turn = MISSILE_TURN_RATE * time.delta_seconds();
+                if error.abs() <= max_turn && time.delta_seconds() > 0. {
+                    error / time.delta_seconds()
+                } else {
+                    MISSILE_TURN_RATE * error.signum()
+                }
+            }
+            None => 0.,
+        };
+
+        if !missile.fuel.finished() {
+            ext_force.force += heading * MISSILE_THRUST;
+        }
+    }
+}
+
+fn missile_detonation_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut collision_events: EventReader<CollisionEvent>,
+    spatial_index: Res<SpatialIndex>,
+    mut missile_query: Query<(Entity, &Transform, &mut Missile, &FromPlayer)>,
+    shot_query: Query<&FromPlayer>,
+    ship_query: Query<&PlayerId, With<Player>>,
+) {
+    // the ship that fired it and that ship's other shots pass by without setting it off
+    let owned_by = |entity: Entity, owner: PlayerId| {
+        matches!(shot_query.get(entity), Ok(from_player) if from_player.0 == owner)
+            || matches!(ship_query.get(entity), Ok(id) if *id == owner)
+    };
+
+    // missiles touching anything else solid go off straight away, sensors like pickups are ignored
+    let mut collided: HashSet<Entity> = HashSet::new();
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(entity_1, entity_2, flags) = event {
+            if flags.contains(CollisionEventFlags::SENSOR) {
+                continue;
+            }
+            for (missile, other) in [(*entity_1, *entity_2), (*entity_2, *entity_1)] {
+                if let Ok((.., from_player)) = missile_query.get(missile) {
+                    if !owned_by(other, from_player.0) {
+                        collided.insert(missile);
+                    }
+                }
+            }
+        }
+    }
+
+    for (missile_entity, missile_tf, mut missile, from_player) in missile_query.iter_mut() {
+        missile.fuel.tick(time.delta());
+
+        let position = missile_tf.translation.xy();
+        let near_enemy = spatial_index
+            .within_radius(SpatialLayer::Enemy, position, MISSILE_PROXIMITY_FUSE)
+            .next()
+            .is_some();
+
+        if missile.fuel.finished() || near_enemy || collided.contains(&missile_entity) {
+            commands.entity(missile_entity).despawn();
+            commands.spawn((
+                ExplosionToSpawn(missile_tf.translation),
+                AreaDamage {
+                    radius: MISSILE_BLAST_RADIUS,
+                    owner: Some(from_player.0),
+                },
+            ));
+        }
+    }
+}
@@ -0,0 +1,522 @@
+use crate::{
+    bounds::boundary_system,
+    components::{Asteroid, GravitySource},
+    gravity_forces,
+    telemetry::{SystemTimings, Timed},
+    BoundaryMode, WorldBounds, EXTRA_GRAVITY, G, GRAVITY_SOURCE_MIN_DISTANCE, ORBIT_MAX_SUBSTEPS,
+    ORBIT_SUBSTEP, SOFT_BOUNDARY_STIFFNESS,
+};
+use bevy::{ecs::system::SystemParam, math::Vec3Swizzles, prelude::*};
+use bevy_rapier2d::prelude::{
+    Collider, RapierConfiguration, RapierContext, ReadMassProperties, RigidBody, TimestepMode,
+    Velocity,
+};
+use std::marker::PhantomData;
+
+pub struct OrbitPlugin;
+
+impl Plugin for OrbitPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(OrbitIntegrator::default())
+            .insert_resource(OrbitStats::default())
+            .add_system(orbit_mode_system)
+            // both move asteroids, the leapfrog has the last word on the celestial ones
+            .add_system(
+                leapfrog_system
+                    .after(orbit_mode_system)
+                    .after(boundary_system),
+            )
+            .add_system(orbit_stats_system.after(leapfrog_system));
+    }
+}
+
+/// What moves the asteroids. Gravity fed to rapier as a force lets orbits wander and
+/// gain energy over time, the leapfrog integrator keeps them closed.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrbitIntegrator {
+    #[default]
+    Rapier,
+    /// asteroids become kinematic and stop colliding with each other, everything else
+    /// still bounces off them
+    Leapfrog,
+}
+
+impl OrbitIntegrator {
+    pub fn next(&self) -> Self {
+        match self {
+            OrbitIntegrator::Rapier => OrbitIntegrator::Leapfrog,
+            OrbitIntegrator::Leapfrog => OrbitIntegrator::Rapier,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OrbitIntegrator::Rapier => "rapier",
+            OrbitIntegrator::Leapfrog => "leapfrog",
+        }
+    }
+}
+
+/// An asteroid moved by `leapfrog_system` rather than rapier, which only sees it as a
+/// kinematic body.
+#[derive(Component)]
+pub struct Celestial {
+    pub velocity: Vec2,
+    /// as rapier had it before the body went kinematic
+    mass: f32,
+    /// no longer kept apart by rapier, so pull as if touching once they overlap
+    radius: f32,
+}
+
+/// Energy and angular momentum of the leapfrogged asteroids, which should hold steady.
+/// Both are measured again from scratch whenever an asteroid or gravity source comes or
+/// goes, since that changes them for real.
+#[derive(Resource, Default)]
+pub struct OrbitStats {
+    pub bodies: usize,
+    pub energy: f64,
+    /// about the middle of the world, so a body wrapping over a seam makes it jump
+    pub angular_momentum: f64,
+    /// (bodies, sources, energy, angular momentum) when measuring started
+    start: Option<(usize, usize, f64, f64)>,
+}
+
+impl OrbitStats {
+    /// energy gained since measuring started, as a fraction of what there was
+    pub fn energy_drift(&self) -> f64 {
+        match self.start {
+            Some((.., energy, _)) if energy != 0. => (self.energy - energy) / energy.abs(),
+            _ => 0.,
+        }
+    }
+
+    /// angular momentum gained since measuring started, it starts out near zero so this
+    /// is not a fraction
+    pub fn angular_momentum_drift(&self) -> f64 {
+        match self.start {
+            Some((.., angular_momentum)) => self.angular_momentum - angular_momentum,
+            None => 0.,
+        }
+    }
+}
+
+/// Asteroids rapier is still moving.
+type RapierAsteroid = (With<Asteroid>, Without<Celestial>);
+
+/// Hands asteroids over to the leapfrog or back to rapier, keeping their velocity.
+fn orbit_mode_system(
+    mut commands: Commands,
+    integrator: Res<OrbitIntegrator>,
+    rapier_query: Query<(Entity, &Velocity, &ReadMassProperties, &Collider), RapierAsteroid>,
+    mut celestial_query: Query<(Entity, &Celestial, &mut Velocity)>,
+) {
+    match *integrator {
+        OrbitIntegrator::Leapfrog => {
+            for (entity, velocity, mass_props, collider) in rapier_query.iter() {
+                // rapier works the mass out from the collider after the first step
+                if mass_props.0.mass <= 0. {
+                    continue;
+                }
+                commands
+                    .entity(entity)
+                    .insert(RigidBody::KinematicPositionBased)
+                    .insert(Celestial {
+                        velocity: velocity.linvel,
+                        mass: mass_props.0.mass,
+                        radius: collider.as_ball().map_or(0., |ball| ball.radius()),
+                    });
+            }
+        }
+        OrbitIntegrator::Rapier => {
+            for (entity, celestial, mut velocity) in celestial_query.iter_mut() {
+                velocity.linvel = celestial.velocity;
+                commands
+                    .entity(entity)
+                    .insert(RigidBody::Dynamic)
+                    .remove::<Celestial>();
+            }
+        }
+    }
+}
+
+/// Rapier's settings, and its bodies for moving wrapped asteroids straight over.
+#[derive(SystemParam)]
+struct Rapier<'w, 's> {
+    config: Res<'w, RapierConfiguration>,
+    context: ResMut<'w, RapierContext>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// Steps the asteroids in fixed `ORBIT_SUBSTEP`s of kick, drift, kick, pulled by each
+/// other and the gravity sources. Ships are far too light to move them, so are left out
+/// and the system stays closed. Being kinematic, they are kept in the world here rather
+/// than by `boundary_system` or the walls.
+fn leapfrog_system(
+    time: Res<Time>,
+    mut rapier: Rapier,
+    world_bounds: Res<WorldBounds>,
+    source_query: Query<(&Transform, &GravitySource)>,
+    mut celestial_query: Query<(Entity, &mut Transform, &mut Celestial), Without<GravitySource>>,
+    mut unspent: Local<f32>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Gravity);
+    // keep time with rapier, which stands still while paused
+    if !rapier.config.physics_pipeline_active {
+        return;
+    }
+    *unspent += match rapier.config.timestep_mode {
+        TimestepMode::Fixed { dt, .. } => dt,
+        TimestepMode::Variable {
+            max_dt, time_scale, ..
+        } => (time.delta_seconds() * time_scale).min(max_dt),
+        TimestepMode::Interpolated { time_scale, .. } => time.delta_seconds() * time_scale,
+    };
+    let steps = ((*unspent / ORBIT_SUBSTEP) as u32).min(ORBIT_MAX_SUBSTEPS);
+    // a slow frame drops the time it could not catch up on rather than piling it up
+    *unspent = (*unspent - steps as f32 * ORBIT_SUBSTEP).min(ORBIT_SUBSTEP);
+    if steps == 0 {
+        return;
+    }
+
+    let mut bodies: Vec<(Entity, Vec2, f32, f32)> = celestial_query
+        .iter()
+        .map(|(entity, tf, celestial)| {
+            let position = tf.translation.xy();
+            (entity, position, celestial.mass, celestial.radius)
+        })
+        .collect();
+    let mut velocities: Vec<Vec2> = celestial_query
+        .iter()
+        .map(|(_, _, celestial)| celestial.velocity)
+        .collect();
+    let sources: Vec<(Vec2, f32)> = source_query
+        .iter()
+        .map(|(source_tf, source)| (source_tf.translation.xy(), source.mass))
+        .collect();
+
+    leapfrog(&world_bounds, &mut bodies, &mut velocities, &sources, steps);
+
+    // rapier picks the new transform up as where the kinematic body goes next
+    let scale = rapier.context.physics_scale();
+    for ((entity, position, ..), velocity) in bodies.into_iter().zip(velocities) {
+        if let Ok((_, mut tf, mut celestial)) = celestial_query.get_mut(entity) {
+            // going there would sweep it back across the whole world, knocking everything
+            // in the way, so a body that wrapped is put straight there instead
+            let wrapped = (position - tf.translation.xy())
+                .abs()
+                .cmpgt(world_bounds.size / 2.)
+                .any();
+            let handle = rapier.context.entity2body().get(&entity).copied();
+            if let Some(rb) = handle
+                .filter(|_| wrapped)
+                .and_then(|handle| rapier.context.bodies.get_mut(handle))
+            {
+                let mut pose = *rb.position();
+                pose.translation.vector = (position / scale).into();
+                rb.set_position(pose, true);
+                rb.set_next_kinematic_position(pose);
+            }
+            tf.translation.x = position.x;
+            tf.translation.y = position.y;
+            celestial.velocity = velocity;
+        }
+    }
+}
+
+/// `steps` substeps of bodies as (entity, position, mass, radius) with their velocities.
+fn leapfrog(
+    world_bounds: &WorldBounds,
+    bodies: &mut [(Entity, Vec2, f32, f32)],
+    velocities: &mut [Vec2],
+    sources: &[(Vec2, f32)],
+    steps: u32,
+) {
+    let mut acceleration = accelerations(world_bounds, bodies, sources);
+    for _ in 0..steps {
+        for ((_, position, _, radius), (velocity, acceleration)) in bodies
+            .iter_mut()
+            .zip(velocities.iter_mut().zip(&acceleration))
+        {
+            *velocity += *acceleration * ORBIT_SUBSTEP / 2.;
+            *position += *velocity * ORBIT_SUBSTEP;
+            confine(world_bounds, position, velocity, *radius);
+        }
+        acceleration = accelerations(world_bounds, bodies, sources);
+        for (velocity, acceleration) in velocities.iter_mut().zip(&acceleration) {
+            *velocity += *acceleration * ORBIT_SUBSTEP / 2.;
+        }
+    }
+}
+
+/// Gravity on each body plus the push back in from a soft edge, the same as
+/// `boundary_system` gives everything else.
+fn accelerations(
+    world_bounds: &WorldBounds,
+    bodies: &[(Entity, Vec2, f32, f32)],
+    sources: &[(Vec2, f32)],
+) -> Vec<Vec2> {
+    let rect = world_bounds.rect();
+    gravity_forces(world_bounds, bodies, sources)
+        .into_iter()
+        .zip(bodies)
+        .map(|(force, (_, position, mass, _))| {
+            let mut acceleration = force / *mass;
+            if world_bounds.mode == BoundaryMode::Soft {
+                let outside = *position - position.clamp(rect.min, rect.max);
+                acceleration -= outside * SOFT_BOUNDARY_STIFFNESS;
+            }
+            acceleration
+        })
+        .collect()
+}
+
+/// Wraps a body round, or bounces it off the walls it would otherwise pass through.
+fn confine(world_bounds: &WorldBounds, position: &mut Vec2, velocity: &mut Vec2, radius: f32) {
+    match world_bounds.mode {
+        BoundaryMode::Wrap => *position = world_bounds.wrap(*position),
+        BoundaryMode::Walls => {
+            let reach = (world_bounds.size / 2. - radius).max(Vec2::ZERO);
+            for axis in 0..2 {
+                if position[axis].abs() > reach[axis] {
+                    // reflected back in, as far as it went past
+                    let side = position[axis].signum();
+                    position[axis] = 2. * side * reach[axis] - position[axis];
+                    velocity[axis] = -side * velocity[axis].abs();
+                }
+            }
+        }
+        BoundaryMode::Soft => {}
+    }
+}
+
+fn orbit_stats_system(
+    integrator: Res<OrbitIntegrator>,
+    world_bounds: Res<WorldBounds>,
+    mut stats: ResMut<OrbitStats>,
+    source_query: Query<(&Transform, &GravitySource)>,
+    celestial_query: Query<(&Transform, &Celestial)>,
+) {
+    if *integrator != OrbitIntegrator::Leapfrog {
+        if stats.start.is_some() {
+            *stats = OrbitStats::default();
+        }
+        return;
+    }
+
+    let bodies: Vec<(Vec2, Vec2, f64, f64)> = celestial_query
+        .iter()
+        .map(|(tf, celestial)| {
+            let (mass, radius) = (celestial.mass as f64, celestial.radius as f64);
+            (tf.translation.xy(), celestial.velocity, mass, radius)
+        })
+        .collect();
+    let sources: Vec<(Vec2, f64)> = source_query
+        .iter()
+        .map(|(source_tf, source)| (source_tf.translation.xy(), source.mass as f64))
+        .collect();
+
+    let (energy, angular_momentum) = energy_and_angular_momentum(&world_bounds, &bodies, &sources);
+
+    let counts = (bodies.len(), sources.len());
+    match stats.start {
+        Some((bodies, sources, ..)) if (bodies, sources) == counts => {}
+        _ => stats.start = Some((counts.0, counts.1, energy, angular_momentum)),
+    }
+    stats.bodies = bodies.len();
+    stats.energy = energy;
+    stats.angular_momentum = angular_momentum;
+}
+
+/// Of bodies as (position, velocity, mass, radius) in the pull of each other and the sources.
+fn energy_and_angular_momentum(
+    world_bounds: &WorldBounds,
+    bodies: &[(Vec2, Vec2, f64, f64)],
+    sources: &[(Vec2, f64)],
+) -> (f64, f64) {
+    // the potentials of the forces in `gravity_rows`
+    let strength = (G * EXTRA_GRAVITY) as f64;
+    let mut energy = 0.;
+    let mut angular_momentum = 0.;
+    for (n_1, (position_1, velocity_1, mass_1, radius_1)) in bodies.iter().enumerate() {
+        energy += 0.5 * mass_1 * velocity_1.length_squared() as f64;
+        angular_momentum += mass_1 * position_1.perp_dot(*velocity_1) as f64;
+
+        for (position_2, _, mass_2, radius_2) in bodies.iter().skip(n_1 + 1) {
+            let distance = world_bounds.offset(*position_1, *position_2).length() as f64;
+            let pull = strength * mass_1 * mass_2;
+            energy -= softened_potential(pull, distance, radius_1 + radius_2);
+        }
+        for (source_position, source_mass) in sources.iter() {
+            let distance = world_bounds.offset(*position_1, *source_position).length() as f64;
+            let pull = strength * mass_1 * source_mass;
+            energy -= softened_potential(pull, distance, GRAVITY_SOURCE_MIN_DISTANCE as f64);
+        }
+    }
+    (energy, angular_momentum)
+}
+
+/// Depth of the well a pull of `pull / distance²` sits in, where the pull stops growing
+/// inside `min_distance` so the well deepens in a line.
+fn softened_potential(pull: f64, distance: f64, min_distance: f64) -> f64 {
+    if distance >= min_distance {
+        pull / distance
+    } else {
+        2. * pull / min_distance - pull * distance / min_distance.powi(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::tasks::{ComputeTaskPool, TaskPool};
+    use bevy_rapier2d::prelude::{NoUserData, RapierPhysicsPlugin};
+
+    /// (entity, position, mass, radius) and velocity of each body, for `leapfrog`
+    type Bodies = (Vec<(Entity, Vec2, f32, f32)>, Vec<Vec2>);
+
+    fn bodies(bodies: &[(Vec2, Vec2, f32, f32)]) -> Bodies {
+        // the gravity solver spreads its work over the pool
+        ComputeTaskPool::init(TaskPool::default);
+        bodies
+            .iter()
+            .enumerate()
+            .map(|(n, (position, velocity, mass, radius))| {
+                let body = (Entity::from_raw(n as u32), *position, *mass, *radius);
+                (body, *velocity)
+            })
+            .unzip()
+    }
+
+    fn energy(world_bounds: &WorldBounds, bodies: &Bodies, sources: &[(Vec2, f32)]) -> f64 {
+        let (bodies, velocities) = bodies;
+        let bodies: Vec<(Vec2, Vec2, f64, f64)> = bodies
+            .iter()
+            .zip(velocities)
+            .map(|((_, position, mass, radius), velocity)| {
+                (*position, *velocity, *mass as f64, *radius as f64)
+            })
+            .collect();
+        let sources: Vec<(Vec2, f64)> = sources
+            .iter()
+            .map(|(position, mass)| (*position, *mass as f64))
+            .collect();
+        energy_and_angular_momentum(world_bounds, &bodies, &sources).0
+    }
+
+    #[test]
+    fn leapfrog_holds_the_energy_of_an_orbit_steady() {
+        let world_bounds = WorldBounds::default();
+        let sources = [(Vec2::ZERO, 1000.)];
+        // about the speed for a circle round the source, nudged so the orbits are eccentric
+        let speed = |distance: f32| (G * EXTRA_GRAVITY * 1000. / distance).sqrt();
+        let mut orbiting = bodies(&[
+            (
+                Vec2::new(200., 0.),
+                Vec2::new(0., speed(200.) * 1.1),
+                10.,
+                5.,
+            ),
+            (
+                Vec2::new(-350., 50.),
+                Vec2::new(0., -speed(350.) * 0.9),
+                4.,
+                3.,
+            ),
+            (Vec2::new(0., 500.), Vec2::new(speed(500.), 0.), 1., 2.),
+        ]);
+        let start = energy(&world_bounds, &orbiting, &sources);
+
+        // ten seconds of substeps, a good part of the way round
+        let (bodies, velocities) = &mut orbiting;
+        leapfrog(&world_bounds, bodies, velocities, &sources, 2400);
+        let end = energy(&world_bounds, &orbiting, &sources);
+
+        let drift = (end - start) / start.abs();
+        assert!(drift.abs() < 1e-4, "energy drifted by {drift:e}");
+        // and they did actually move
+        assert!(orbiting.0[0].1.distance(Vec2::new(200., 0.)) > 100.);
+    }
+
+    #[test]
+    fn walls_bounce_bodies_back_in() {
+        let world_bounds = WorldBounds {
+            size: Vec2::new(1000., 800.),
+            mode: BoundaryMode::Walls,
+        };
+        let mut moving = bodies(&[(Vec2::new(480., 0.), Vec2::new(240., 0.), 1., 10.)]);
+        let (bodies, velocities) = &mut moving;
+        leapfrog(&world_bounds, bodies, velocities, &[], 240);
+
+        // a second on, it has come off the wall with its speed kept
+        assert!(bodies[0].1.x <= 490.);
+        assert!((bodies[0].1.x - 260.).abs() < 1e-2, "at {}", bodies[0].1.x);
+        assert_eq!(velocities[0], Vec2::new(-240., 0.));
+    }
+
+    #[test]
+    fn a_ship_riding_along_a_wrapping_asteroid_keeps_its_speed() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(2.))
+            .insert_resource(RapierConfiguration {
+                gravity: Vec2::ZERO,
+                timestep_mode: TimestepMode::Fixed {
+                    dt: 1. / 60.,
+                    substeps: 1,
+                },
+                ..default()
+            })
+            .insert_resource(WorldBounds {
+                size: Vec2::new(1000., 800.),
+                mode: BoundaryMode::Wrap,
+            })
+            .insert_resource(SystemTimings::default())
+            .insert_resource(OrbitIntegrator::Leapfrog)
+            .add_plugin(OrbitPlugin);
+        ComputeTaskPool::init(TaskPool::default);
+
+        let velocity = Vec2::new(120., 0.);
+        let asteroid = app
+            .world
+            .spawn((
+                RigidBody::KinematicPositionBased,
+                Collider::ball(20.),
+                Celestial {
+                    velocity,
+                    mass: 100.,
+                    radius: 20.,
+                },
+                TransformBundle::from(Transform::from_xyz(470., 0., 0.)),
+            ))
+            .id();
+        // just touching the asteroid's back as it nears the seam
+        let ship = app
+            .world
+            .spawn((
+                RigidBody::Dynamic,
+                Collider::ball(10.),
+                Velocity::linear(velocity),
+                TransformBundle::from(Transform::from_xyz(440., 0., 0.)),
+            ))
+            .id();
+
+        // half a second, the asteroid goes over the seam a quarter of the way in
+        for _ in 0..30 {
+            app.update();
+        }
+        let asteroid_x = app.world.get::<Transform>(asteroid).unwrap().translation.x;
+        assert!(
+            asteroid_x < 0.,
+            "the asteroid did not wrap, at {asteroid_x}"
+        );
+        let ship_velocity = app.world.get::<Velocity>(ship).unwrap().linvel;
+        assert!(
+            ship_velocity.distance(velocity) < 1.,
+            "the ship was knocked to {ship_velocity}"
+        );
+    }
+}
@@ -1,161 +1,352 @@
-use std::f32::consts::PI;
+use std::{
+    f32::consts::{PI, TAU},
+    marker::PhantomData,
+};
 
 use crate::{
-    components::{FromPlayer, Laser, Movable, Player, SpriteSize, Velocity, Orientation},
-    GameTextures, PlayerState, WinSize, PLAYER_LASER_SIZE, PLAYER_RESPAWN_DELAY, PLAYER_SIZE,
-    SPRITE_SCALE, MyGamepad, LASER_VELOCITY,
+    apply_gravitational_forces,
+    components::{DespawnOutOfBounds, FromPlayer, Laser, Lifetime, Movable, Player, PlayerId, SpriteSize, Velocity, Orientation, WeaponHeat, MissileLauncher, ActivePowerUps, PowerUpKind, GravityAssist},
+    controls::{Action, ActionState},
+    devices::DeviceManager,
+    haptics::RumbleEvent,
+    telemetry::{SystemTimings, Timed},
+    GameTextures, Players, WinSize, PLAYER_LASER_SIZE, PLAYER_RESPAWN_DELAY, PLAYER_SIZE,
+    SPRITE_SCALE, LASER_VELOCITY, MUZZLE_DISTANCE, WEAPON_UPGRADE_SPREAD, PLAYER_LIVES, FIRE_RATE, HEAT_PER_SHOT, HEAT_COOL_RATE,
+    OVERHEAT_RECOVER, PRIMARY_THRUST, SECONDARY_THRUST, THRUST_BOOST_FACTOR,
+    PLAYER_TURN_RATE, PLAYER_SPAWN_SPACING, RUMBLE_FIRE, LASER_LIFETIME, TURN_STIFFNESS, TURN_DAMPING,
+    TURN_MAX_ACCELERATION, ANGULAR_DAMPING,
 };
-use bevy::{prelude::*, time::FixedTimestep};
-use bevy_rapier2d::prelude::{RigidBody, Collider, ExternalForce, Restitution, ReadMassProperties, MassProperties, ColliderMassProperties, ExternalImpulse};
+use bevy::{ecs::system::SystemParam, prelude::*, time::FixedTimestep};
+use bevy_rapier2d::prelude::{Velocity as RapierVelocity, ActiveEvents, RigidBody, Collider, ExternalForce, Restitution, ReadMassProperties, MassProperties, ColliderMassProperties, ExternalImpulse, LockedAxes, Damping, RapierContext};
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(PlayerState::default())
+        app.insert_resource(Players::default())
+            .insert_resource(RotationModel::default())
+            .insert_resource(RotationGains::default())
             .add_system_set(
                 SystemSet::new()
                     .with_run_criteria(FixedTimestep::step(0.5))
                     .with_system(player_spawn_system),
             )
-            .add_system(player_keyboard_event_system)
-            .add_system(player_fire_system);
+            .add_system(player_leave_system)
+            .add_system(rotation_model_system)
+            .add_system(player_control_system.after(apply_gravitational_forces))
+            .add_system(player_fire_system)
+            .add_system(weapon_cooling_system);
+    }
+}
+
+/// How the ship turns towards the aim direction.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RotationModel {
+    /// the physics never rotates the ship, its nose is turned directly
+    #[default]
+    Arcade,
+    /// the physics owns the rotation and thrusters torque the ship round, so it
+    /// overshoots, can be spun by collisions and keeps spinning until damped
+    Newtonian,
+}
+
+impl RotationModel {
+    pub fn next(&self) -> Self {
+        match self {
+            RotationModel::Arcade => RotationModel::Newtonian,
+            RotationModel::Newtonian => RotationModel::Arcade,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RotationModel::Arcade => "Arcade",
+            RotationModel::Newtonian => "Newtonian",
+        }
+    }
+
+    fn locked_axes(&self) -> LockedAxes {
+        match self {
+            RotationModel::Arcade => LockedAxes::ROTATION_LOCKED,
+            RotationModel::Newtonian => LockedAxes::empty(),
+        }
     }
 }
 
+/// Gains of the newtonian turning controller, adjustable from the settings menu.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct RotationGains {
+    pub stiffness: f32,
+    pub damping: f32,
+    pub angular_damping: f32,
+}
+
+impl Default for RotationGains {
+    fn default() -> Self {
+        Self {
+            stiffness: TURN_STIFFNESS,
+            damping: TURN_DAMPING,
+            angular_damping: ANGULAR_DAMPING,
+        }
+    }
+}
+
+/// What a new ship is made from.
+#[derive(SystemParam)]
+struct ShipSetup<'w, 's> {
+    rotation_model: Res<'w, RotationModel>,
+    gains: Res<'w, RotationGains>,
+    game_textures: Res<'w, GameTextures>,
+    win_size: Res<'w, WinSize>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// Spawns a ship for every player with a device, once their respawn delay is up.
 fn player_spawn_system(
     mut commands: Commands,
-    mut player_state: ResMut<PlayerState>,
+    mut players: ResMut<Players>,
+    setup: ShipSetup,
+    device_manager: Res<DeviceManager>,
     time: Res<Time>,
-    game_textures: Res<GameTextures>,
-    win_size: Res<WinSize>,
+    timings: Res<SystemTimings>,
 ) {
+    let _timer = timings.start(Timed::Spawn);
+    let ShipSetup {
+        rotation_model,
+        gains,
+        game_textures,
+        win_size,
+        ..
+    } = setup;
     let now = time.elapsed_seconds_f64();
-    let last_shot = player_state.last_shot;
-
-    if !player_state.on && (last_shot == -1. || now > last_shot + PLAYER_RESPAWN_DELAY) {
-        // reset score
-        player_state.score = 0;
-
-        // add player
-        let bottom = -win_size.h / 2.;
-        commands
-            .spawn(SpriteBundle {
-                texture: game_textures.player.clone(),
-                transform: Transform {
-                    // vec3::new(x, y (+ padding), z)
-                    translation: Vec3::new(
-                        0.,
-                        bottom + PLAYER_SIZE.1 / 2. * SPRITE_SCALE + 5.,
-                        10.,
-                    ),
-                    scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
-                    ..Default::default()
-                },
 
-                /* // add rectangle
-                sprite: Sprite {
-                    color: Color::rgb(0.25, 0.25, 0.75),
-                    custom_size: Some(Vec2::new(150.0, 150.0)),
+    for id in device_manager.players.keys() {
+        let player_state = players.state(*id);
+        let last_shot = player_state.last_shot;
+
+        if !player_state.on && (last_shot == -1. || now > last_shot + PLAYER_RESPAWN_DELAY) {
+            // out of lives, start a new game
+            if player_state.lives == 0 {
+                player_state.score = 0;
+                player_state.lives = PLAYER_LIVES;
+            }
+
+            // add player, P1 in the middle and the others alternating either side
+            let bottom = -win_size.h / 2.;
+            let lane = id.0.div_ceil(2) as f32 * PLAYER_SPAWN_SPACING;
+            let x = if id.0 % 2 == 1 { lane } else { -lane };
+            commands
+                .spawn(SpriteBundle {
+                    texture: game_textures.player.clone(),
+                    transform: Transform {
+                        // vec3::new(x, y (+ padding), z)
+                        translation: Vec3::new(
+                            x,
+                            bottom + PLAYER_SIZE.1 / 2. * SPRITE_SCALE + 5.,
+                            10.,
+                        ),
+                        scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
+                        ..Default::default()
+                    },
+
+                    sprite: Sprite {
+                        color: id.color(),
+                        ..Default::default()
+                    },
+
+                    /* // add rectangle
+                    sprite: Sprite {
+                        color: Color::rgb(0.25, 0.25, 0.75),
+                        custom_size: Some(Vec2::new(150.0, 150.0)),
+                        ..Default::default()
+                    }, */
                     ..Default::default()
-                }, */
-                ..Default::default()
-            })
-            .insert(Player)
-            .insert(SpriteSize::from(PLAYER_SIZE))
-            .insert(Velocity { x: 0., y: 0. })
-            .insert(Orientation::default())
-            .insert(Movable {
-                auto_despawn: false,
-            })
-            // PLAYER PHYICS
-            .insert(RigidBody::Dynamic)
-            .insert(Collider::triangle(Vec2::new(-50.0, -30.0), Vec2::new(50.0, -30.0), Vec2::new(0.0, 40.0)))
-            .insert(Restitution::coefficient(0.7))
-            .insert(ExternalForce {
-                force: Vec2::new(0., 0.),
-                torque: 0.
-            });
-            player_state.spawned();
+                })
+                .insert(Player)
+                .insert(*id)
+                .insert(SpriteSize::from(PLAYER_SIZE))
+                .insert(Velocity { x: 0., y: 0. })
+                .insert(Orientation::default())
+                .insert(WeaponHeat::default())
+                .insert(MissileLauncher::default())
+                .insert(ActivePowerUps::default())
+                .insert(Movable {
+                    auto_despawn: false,
+                })
+                // PLAYER PHYICS
+                .insert(RigidBody::Dynamic)
+                .insert(Collider::triangle(Vec2::new(-50.0, -30.0), Vec2::new(50.0, -30.0), Vec2::new(0.0, 40.0)))
+                .insert(Restitution::coefficient(0.7))
+                .insert(ExternalForce {
+                    force: Vec2::new(0., 0.),
+                    torque: 0.
+                })
+                // gravity pulls on the ship too, which makes slingshots possible
+                .insert(ReadMassProperties(MassProperties {
+                    ..Default::default()
+                }))
+                .insert(RapierVelocity::zero())
+                .insert(rotation_model.locked_axes())
+                .insert(Damping {
+                    linear_damping: 0.,
+                    angular_damping: gains.angular_damping,
+                })
+                .insert(GravityAssist::default());
+                player_state.spawned();
+        }
     }
 }
 
-fn player_keyboard_event_system(
-    kb: Res<Input<KeyCode>>,
-    mut query: Query<(&mut Velocity, &mut Orientation), With<Player>>,
+/// Removes the ship and state of players that left the game.
+fn player_leave_system(
+    mut commands: Commands,
+    mut players: ResMut<Players>,
+    device_manager: Res<DeviceManager>,
+    query: Query<(Entity, &PlayerId), With<Player>>,
 ) {
-    for (mut velocity, mut orientation) in query.iter_mut() {
+    if !device_manager.is_changed() {
+        return;
+    }
 
-        // WASD
-        velocity.x = if kb.pressed(KeyCode::A) {
-            -1.
-        } else if kb.pressed(KeyCode::D) {
-            1.
-        } else {
-            0.
-        };
+    for (entity, id) in query.iter() {
+        if !device_manager.players.contains_key(id) {
+            commands.entity(entity).despawn();
+        }
+    }
+    players
+        .0
+        .retain(|id, _| device_manager.players.contains_key(id));
+}
 
-        velocity.y = if kb.pressed(KeyCode::W) {
-            1.
-        } else if kb.pressed(KeyCode::S) {
-            -1.
+/// Switches the ships over when the rotation model or its damping changes.
+fn rotation_model_system(
+    rotation_model: Res<RotationModel>,
+    gains: Res<RotationGains>,
+    mut query: Query<(&mut LockedAxes, &mut Damping, &mut RapierVelocity), With<Player>>,
+) {
+    if !rotation_model.is_changed() && !gains.is_changed() {
+        return;
+    }
+
+    for (mut locked_axes, mut damping, mut velocity) in query.iter_mut() {
+        *locked_axes = rotation_model.locked_axes();
+        damping.angular_damping = gains.angular_damping;
+        // a locked ship keeps whatever spin it had otherwise
+        if *rotation_model == RotationModel::Arcade {
+            velocity.angvel = 0.;
+        }
+    }
+}
+
+type ShipControl = (
+    &'static PlayerId,
+    &'static mut ExternalForce,
+    &'static mut Transform,
+    &'static mut Orientation,
+    &'static RapierVelocity,
+    &'static ReadMassProperties,
+    &'static ActivePowerUps,
+);
+
+/// Turns the strafe, thrust and aim actions into forces on the ship, added on
+/// top of the gravity already written into `ExternalForce`.
+fn player_control_system(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    rotation_model: Res<RotationModel>,
+    gains: Res<RotationGains>,
+    rapier_context: Res<RapierContext>,
+    mut query: Query<ShipControl, With<Player>>,
+) {
+    for (id, mut ext_force, mut transform, mut orientation, velocity, mass_props, power_ups) in
+        query.iter_mut()
+    {
+        let actions = actions.player(*id);
+        let boost = if power_ups.is_active(PowerUpKind::ThrustBoost) {
+            THRUST_BOOST_FACTOR
         } else {
-            0.
+            1.
         };
 
-        // DIRECTION w/ arrow keys
-        orientation.theta = if kb.pressed(KeyCode::Up) {
-            0.
-        } else if kb.pressed(KeyCode::Down) {
-            PI
-        } else if kb.pressed(KeyCode::Right) {
-            3. * PI / 2.
-        } else if kb.pressed(KeyCode::Left) {
-            PI / 2.
+        // a diagonal on the keys pushes no harder than a full stick
+        let strafe = actions.axis(Action::Strafe).clamp_length_max(1.);
+        let mut thrust_force = strafe * SECONDARY_THRUST;
+        if actions.pressed(Action::Thrust) {
+            let theta = orientation.theta;
+            thrust_force = Vec2::new(-theta.sin(), theta.cos()) * PRIMARY_THRUST;
         }
-        else { continue };
-
+        ext_force.force += thrust_force * boost;
 
+        // the sprite starts rotated by Pi / 2, no aim keeps the current heading
+        let aim = actions.axis(Action::Aim);
+        let target = (aim != Vec2::ZERO).then(|| aim.y.atan2(aim.x) - PI / 2.);
+        // shortest way round, from -Pi to Pi
+        let wrap = |angle: f32| (angle + PI).rem_euclid(TAU) - PI;
 
+        match *rotation_model {
+            RotationModel::Arcade => {
+                // turn the nose towards the aim direction no faster than the turn rate
+                ext_force.torque = 0.;
+                if let Some(target) = target {
+                    let error = wrap(target - orientation.theta);
+                    let max_turn = PLAYER_TURN_RATE * time.delta_seconds();
+                    let theta = orientation.theta + error.clamp(-max_turn, max_turn);
+                    transform.rotation = Quat::from_rotation_z(theta);
+                    orientation.theta = theta;
+                }
+            }
+            RotationModel::Newtonian => {
+                // read the heading back from the physics, then torque towards the aim
+                // and against the spin, letting go of the stick just stops the spin
+                orientation.theta = transform.rotation.to_euler(EulerRot::ZYX).0;
+                let error = target.map_or(0., |target| wrap(target - orientation.theta));
+                let angular_acceleration = (gains.stiffness * error
+                    - gains.damping * velocity.angvel)
+                    .clamp(-TURN_MAX_ACCELERATION, TURN_MAX_ACCELERATION);
+                // torque goes to rapier unscaled, but the inertia we read was scaled up
+                let inertia =
+                    mass_props.0.principal_inertia / rapier_context.physics_scale().powi(2);
+                ext_force.torque = inertia * angular_acceleration;
+            }
+        }
     }
 }
 
 fn player_fire_system(
     mut commands: Commands,
-    kb: Res<Input<KeyCode>>,
-    button: Res<Input<GamepadButton>>,
-    my_gamepad: Option<Res<MyGamepad>>,
+    time: Res<Time>,
+    actions: Res<ActionState>,
     game_textures: Res<GameTextures>,
-    query: Query<(&Transform, &Orientation), With<Player>>,
+    mut rumble: EventWriter<RumbleEvent>,
+    mut query: Query<
+        (&PlayerId, &Transform, &Orientation, &mut WeaponHeat, &ActivePowerUps),
+        With<Player>,
+    >,
 ) {
+    let now = time.elapsed_seconds_f64();
 
-    // locate connected game pad
-    let gamepad = if let Some(gp) = my_gamepad {
-        // a gamepad is connected, we have the id
-        gp.0
-    } else {
-        // no gamepad is connected
-        return;
-    };
-
-    for (player_tf, orientation) in query.iter() {
-        if kb.just_pressed(KeyCode::Space) || button.pressed(GamepadButton {
-            gamepad, button_type: GamepadButtonType::RightTrigger2
-        }) {
-            let (x, y, theta) = (player_tf.translation.x, player_tf.translation.y, orientation.theta);
-
-            // offset to change where laser fires from
-            let x_offset = PLAYER_SIZE.0 / 4. * SPRITE_SCALE;
-            let y_offset: f32 = 20.;
+    for (id, player_tf, orientation, mut weapon_heat, power_ups) in query.iter_mut() {
+        // hold to fire, the fire rate limiter spaces out the shots
+        if !actions.player(*id).pressed(Action::Fire) {
+            continue;
+        }
 
-            // create closure so multiple lasers can be spawned
-            let mut spawn_laser = |x_offset: f32, y_offset: f32| {
+        if weapon_heat.can_fire(now, 1. / FIRE_RATE) {
+            // create closure so multiple lasers can be spawned, each heading `theta`
+            let mut spawn_laser = |theta: f32| {
+                // the muzzle is ahead of the nose, turned with the laser's heading
+                let muzzle = Quat::from_rotation_z(theta) * Vec3::new(0., MUZZLE_DISTANCE, 0.);
                 commands
                     .spawn(SpriteBundle {
                         texture: game_textures.player_laser.clone(),
+                        sprite: Sprite {
+                            color: id.color(),
+                            ..Default::default()
+                        },
                         transform: Transform {
                             // orient laser spawn relative to player orientation
-                            translation: Vec3::new(x + x_offset*orientation.theta.cos(), y + y_offset*orientation.theta.sin(), 0.),
+                            translation: player_tf.translation.truncate().extend(0.) + muzzle,
                             rotation: Quat::from_rotation_z(theta),
                             scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
                             ..Default::default()
@@ -164,29 +355,50 @@ fn player_fire_system(
                     })
                     .insert(Laser)
                     .insert(ExternalImpulse {
-                        impulse: Vec2::new(- 2.*orientation.theta.sin()*LASER_VELOCITY, 2.*orientation.theta.cos()*LASER_VELOCITY),
+                        impulse: Vec2::new(- 2.*theta.sin()*LASER_VELOCITY, 2.*theta.cos()*LASER_VELOCITY),
                         torque_impulse: 0.,
                     })
+                    .insert(ExternalForce::default())
                     //.insert(Velocity { x: - 2.*orientation.theta.sin(), y: 2.*orientation.theta.cos() }) // laser speed of 2
-                    .insert(FromPlayer)
+                    .insert(FromPlayer(*id))
                     .insert(SpriteSize::from(PLAYER_LASER_SIZE))
                     .insert(Orientation { theta: theta })
                     .insert(RigidBody::Dynamic)
                     .insert(Collider::cuboid(1., 2.))
+                    .insert(ActiveEvents::COLLISION_EVENTS)
+                    .insert(RapierVelocity::zero())
+                    .insert(GravityAssist::default())
                     .insert(Restitution::coefficient(0.0))
                     .insert(ReadMassProperties(MassProperties {
                         ..Default::default()
-                    }));
+                    }))
+                    .insert(Lifetime::from_seconds(LASER_LIFETIME))
+                    .insert(DespawnOutOfBounds);
                     //.insert(ColliderMassProperties::Density(0.01));
             };
 
-            spawn_laser(30., 30.);
-            //spawn_laser(-20., 20.);
+            // spread three lasers either side of the aim while the weapon upgrade is active
+            if power_ups.is_active(PowerUpKind::WeaponUpgrade) {
+                for spread in [-WEAPON_UPGRADE_SPREAD, 0., WEAPON_UPGRADE_SPREAD] {
+                    spawn_laser(orientation.theta + spread);
+                }
+            } else {
+                spawn_laser(orientation.theta);
+            }
 
-            // spawn three lasers
-            //spawn_laser(x_offset, 0.);
-            //spawn_laser(-x_offset, 0.);
-            //spawn_laser(0., y_offset);
+            weapon_heat.fired(now, HEAT_PER_SHOT);
+            let (strength, duration) = RUMBLE_FIRE;
+            rumble.send(RumbleEvent {
+                player: *id,
+                strength,
+                duration,
+            });
         }
     }
 }
+
+fn weapon_cooling_system(time: Res<Time>, mut query: Query<&mut WeaponHeat, With<Player>>) {
+    for mut weapon_heat in query.iter_mut() {
+        weapon_heat.cool(HEAT_COOL_RATE * time.delta_seconds(), OVERHEAT_RECOVER);
+    }
+}
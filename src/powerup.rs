@@ -0,0 +1,132 @@
+use crate::{
+    components::{ActivePowerUps, EnemyKind, Pickup, Player, PlayerId, PowerUpKind, SpriteSize},
+    spatial::{sprite_rect, SpatialIndex, SpatialLayer},
+    EnemyDestroyed, Players, ENEMY_DROP_CHANCE, PICKUP_LIFETIME, PICKUP_RADIUS,
+};
+use bevy::{prelude::*, utils::HashSet};
+use bevy_rapier2d::prelude::{
+    Collider, ExternalForce, MassProperties, ReadMassProperties, RigidBody, Sensor, Velocity,
+};
+use rand::prelude::*;
+
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(enemy_drop_system)
+            .add_system(pickup_lifetime_system)
+            .add_system(pickup_collect_system)
+            .add_system(power_up_timer_system);
+    }
+}
+
+/// Weighted power-ups an enemy kind can drop, rolled after `ENEMY_DROP_CHANCE` succeeds.
+fn drop_table(kind: EnemyKind) -> &'static [(PowerUpKind, u32)] {
+    match kind {
+        EnemyKind::Invader => &[
+            (PowerUpKind::Shield, 3),
+            (PowerUpKind::WeaponUpgrade, 3),
+            (PowerUpKind::ThrustBoost, 2),
+            (PowerUpKind::ScoreMultiplier, 2),
+            (PowerUpKind::ExtraLife, 1),
+        ],
+    }
+}
+
+fn enemy_drop_system(mut commands: Commands, mut enemy_destroyed: EventReader<EnemyDestroyed>) {
+    let mut rng = thread_rng();
+
+    for event in enemy_destroyed.iter() {
+        if !rng.gen_bool(ENEMY_DROP_CHANCE) {
+            continue;
+        }
+        let kind = match drop_table(event.kind).choose_weighted(&mut rng, |(_, weight)| *weight) {
+            Ok((kind, _)) => *kind,
+            Err(_) => continue,
+        };
+
+        // nudge the pickup in a random direction so it drifts off into the gravity field
+        let angle: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+        let drift = Vec2::new(angle.cos(), angle.sin()) * 20.;
+
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: kind.color(),
+                    custom_size: Some(Vec2::splat(PICKUP_RADIUS * 2.)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(event.position),
+                ..Default::default()
+            })
+            .insert(Pickup {
+                kind,
+                lifetime: Timer::from_seconds(PICKUP_LIFETIME, TimerMode::Once),
+            })
+            .insert(RigidBody::Dynamic)
+            .insert(Collider::ball(PICKUP_RADIUS))
+            .insert(Sensor)
+            .insert(Velocity {
+                linvel: drift,
+                angvel: 0.,
+            })
+            .insert(ExternalForce::default())
+            .insert(ReadMassProperties(MassProperties {
+                ..Default::default()
+            }));
+    }
+}
+
+/// Fades pickups out over the last few seconds and removes them when they expire.
+fn pickup_lifetime_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Pickup, &mut Sprite)>,
+) {
+    for (entity, mut pickup, mut sprite) in query.iter_mut() {
+        pickup.lifetime.tick(time.delta());
+        if pickup.lifetime.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let remaining = pickup.lifetime.remaining_secs();
+        sprite.color.set_a((remaining / 3.).min(1.));
+    }
+}
+
+fn pickup_collect_system(
+    mut commands: Commands,
+    mut players: ResMut<Players>,
+    spatial_index: Res<SpatialIndex>,
+    pickup_query: Query<&Pickup>,
+    mut player_query: Query<
+        (&PlayerId, &Transform, &SpriteSize, &mut ActivePowerUps),
+        With<Player>,
+    >,
+) {
+    let mut collected: HashSet<Entity> = HashSet::new();
+
+    for (player_id, player_tf, player_size, mut power_ups) in player_query.iter_mut() {
+        let touching = spatial_index
+            .overlapping(SpatialLayer::Pickup, sprite_rect(player_tf, player_size))
+            .map(|pickup| pickup.entity);
+
+        for pickup_entity in touching {
+            let kind = match pickup_query.get(pickup_entity) {
+                Ok(pickup) if collected.insert(pickup_entity) => pickup.kind,
+                _ => continue,
+            };
+            match kind.duration() {
+                Some(duration) => power_ups.activate(kind, duration),
+                None => players.state(*player_id).lives += 1,
+            }
+            commands.entity(pickup_entity).despawn();
+        }
+    }
+}
+
+fn power_up_timer_system(time: Res<Time>, mut query: Query<&mut ActivePowerUps>) {
+    for mut power_ups in query.iter_mut() {
+        power_ups.tick(time.delta());
+    }
+}
@@ -0,0 +1,386 @@
+use crate::{
+    camera::GameCamera,
+    components::{
+        Asteroid, Enemy, GravitySource, GravityWell, Laser, Missile, Pickup, Player, PlayerId,
+    },
+    Players, WinSize, WorldBounds, EDGE_INDICATOR_INSET, EDGE_INDICATOR_SIZE, RADAR_RANGE,
+    RADAR_SIZE,
+};
+use bevy::{ecs::system::SystemParam, math::Vec3Swizzles, prelude::*};
+use bevy_rapier2d::prelude::ReadMassProperties;
+
+pub struct RadarPlugin;
+
+impl Plugin for RadarPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RadarSettings::default())
+            .add_startup_system(radar_setup_system)
+            // before anything draws on the panels, so none are despawned under it
+            .add_system_to_stage(CoreStage::PreUpdate, radar_panel_system)
+            .add_system(radar_system)
+            .add_system(edge_indicator_system);
+    }
+}
+
+#[derive(Resource)]
+pub struct RadarSettings {
+    pub enabled: bool,
+    /// world distance from the centre to the edge of the radar
+    pub range: f32,
+}
+
+impl Default for RadarSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            range: RADAR_RANGE,
+        }
+    }
+}
+
+/// The row along the bottom of the screen the radars sit in.
+#[derive(Component)]
+struct RadarRow;
+
+/// One player's radar, centred on their ship, with the `PlayerId` alongside.
+#[derive(Component)]
+struct RadarPanel;
+
+/// A dot on the radar, reused from frame to frame and hidden while spare.
+#[derive(Component)]
+struct RadarBlip;
+
+/// Points from the edge of the screen towards an enemy out of view.
+#[derive(Component)]
+struct EdgeIndicator;
+
+/// What a blip stands for, which sets its colour and size.
+enum BlipKind {
+    /// sized by mass
+    Planet(f32),
+    GravityWell,
+    Enemy,
+    Pickup,
+    Projectile,
+    Ship(PlayerId),
+}
+
+impl BlipKind {
+    fn color(&self) -> Color {
+        match self {
+            BlipKind::Planet(_) => Color::rgb(0.6, 0.5, 0.4),
+            BlipKind::GravityWell => Color::PURPLE,
+            BlipKind::Enemy => Color::RED,
+            BlipKind::Pickup => Color::GREEN,
+            BlipKind::Projectile => Color::YELLOW,
+            BlipKind::Ship(id) => id.color(),
+        }
+    }
+
+    fn size(&self) -> f32 {
+        match self {
+            // masses span many orders of magnitude, so grow with the log
+            BlipKind::Planet(mass) => (2. * (mass + 1.).log10()).clamp(4., 16.),
+            BlipKind::GravityWell => 8.,
+            BlipKind::Enemy | BlipKind::Ship(_) => 6.,
+            BlipKind::Pickup => 5.,
+            BlipKind::Projectile => 2.,
+        }
+    }
+}
+
+fn radar_setup_system(mut commands: Commands) {
+    // full width row along the bottom so the radars sit in the middle
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Px(RADAR_SIZE)),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(RadarRow);
+}
+
+/// Gives every player that has joined a radar, rebuilt in player order whenever someone
+/// joins or leaves.
+fn radar_panel_system(
+    mut commands: Commands,
+    players: Res<Players>,
+    row_query: Query<Entity, With<RadarRow>>,
+    panel_query: Query<&PlayerId, With<RadarPanel>>,
+) {
+    let mut ids: Vec<PlayerId> = players.0.keys().copied().collect();
+    ids.sort();
+    let mut shown: Vec<PlayerId> = panel_query.iter().copied().collect();
+    shown.sort();
+    if ids == shown {
+        return;
+    }
+    let row = match row_query.get_single() {
+        Ok(row) => row,
+        Err(_) => return,
+    };
+
+    commands.entity(row).despawn_descendants();
+    commands.entity(row).with_children(|parent| {
+        for id in ids {
+            let tint = id.color();
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(RADAR_SIZE), Val::Px(RADAR_SIZE)),
+                        margin: UiRect::horizontal(Val::Px(5.)),
+                        ..Default::default()
+                    },
+                    // a dark wash of the player's colour
+                    background_color: Color::rgba(
+                        tint.r() * 0.2,
+                        tint.g() * 0.2,
+                        tint.b() * 0.2,
+                        0.6,
+                    )
+                    .into(),
+                    ..Default::default()
+                },
+                RadarPanel,
+                id,
+            ));
+        }
+    });
+}
+
+type IsProjectile = Or<(With<Laser>, With<Missile>)>;
+
+/// Everything that shows up on the radar.
+#[derive(SystemParam)]
+struct RadarTargets<'w, 's> {
+    bodies: Query<'w, 's, (&'static Transform, &'static ReadMassProperties), With<Asteroid>>,
+    sources: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static GravitySource,
+            Option<&'static GravityWell>,
+        ),
+    >,
+    enemies: Query<'w, 's, &'static Transform, With<Enemy>>,
+    pickups: Query<'w, 's, &'static Transform, With<Pickup>>,
+    projectiles: Query<'w, 's, &'static Transform, IsProjectile>,
+    ships: Query<'w, 's, (&'static Transform, &'static PlayerId), With<Player>>,
+}
+
+impl RadarTargets<'_, '_> {
+    /// Every blip as (position, size, colour), drawn in this order so ships end up on top.
+    fn blips(&self) -> Vec<(Vec2, f32, Color)> {
+        let position = |tf: &Transform| tf.translation.xy();
+        let bodies = self
+            .bodies
+            .iter()
+            .map(|(tf, mass_props)| (position(tf), BlipKind::Planet(mass_props.0.mass)));
+        let sources = self.sources.iter().map(|(tf, source, well)| match well {
+            Some(_) => (position(tf), BlipKind::GravityWell),
+            None => (position(tf), BlipKind::Planet(source.mass)),
+        });
+        let pickups = self
+            .pickups
+            .iter()
+            .map(|tf| (position(tf), BlipKind::Pickup));
+        let projectiles = self
+            .projectiles
+            .iter()
+            .map(|tf| (position(tf), BlipKind::Projectile));
+        let enemies = self
+            .enemies
+            .iter()
+            .map(|tf| (position(tf), BlipKind::Enemy));
+        let ships = self
+            .ships
+            .iter()
+            .map(|(tf, id)| (position(tf), BlipKind::Ship(*id)));
+
+        bodies
+            .chain(sources)
+            .chain(pickups)
+            .chain(projectiles)
+            .chain(enemies)
+            .chain(ships)
+            .map(|(thing, kind)| (thing, kind.size(), kind.color()))
+            .collect()
+    }
+
+    fn ship(&self, id: PlayerId) -> Option<Vec2> {
+        self.ships
+            .iter()
+            .find(|(_, ship_id)| **ship_id == id)
+            .map(|(ship_tf, _)| ship_tf.translation.xy())
+    }
+}
+
+type BlipQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Style,
+        &'static mut BackgroundColor,
+        &'static mut Visibility,
+    ),
+    (With<RadarBlip>, Without<RadarRow>),
+>;
+
+/// The row, each player's panel in it and the blips on them.
+#[derive(SystemParam)]
+struct RadarPanels<'w, 's> {
+    row: Query<'w, 's, &'static mut Visibility, With<RadarRow>>,
+    panels: Query<'w, 's, (Entity, &'static PlayerId, Option<&'static Children>), With<RadarPanel>>,
+    blips: BlipQuery<'w, 's>,
+}
+
+/// Each player's radar shows what is around their ship, or around the camera while they
+/// wait to respawn. Spare blips are hidden rather than despawned, new ones are only
+/// spawned when there are more things in range than ever before.
+fn radar_system(
+    mut commands: Commands,
+    settings: Res<RadarSettings>,
+    world_bounds: Res<WorldBounds>,
+    camera_query: Query<&Transform, With<GameCamera>>,
+    targets: RadarTargets,
+    mut radar: RadarPanels,
+) {
+    let mut row_visibility = match radar.row.get_single_mut() {
+        Ok(row_visibility) => row_visibility,
+        Err(_) => return,
+    };
+    if row_visibility.is_visible != settings.enabled {
+        row_visibility.is_visible = settings.enabled;
+    }
+    let camera_centre = match camera_query.get_single() {
+        Ok(camera_tf) if settings.enabled => camera_tf.translation.xy(),
+        _ => return,
+    };
+
+    let things = targets.blips();
+    for (panel, id, children) in radar.panels.iter() {
+        let centre = targets.ship(*id).unwrap_or(camera_centre);
+
+        // each blip as (top left corner on the radar, size, colour)
+        let mut blips = things.iter().filter_map(|(thing, size, color)| {
+            let offset = world_bounds.offset(centre, *thing) / settings.range;
+            if offset.x.abs() > 1. || offset.y.abs() > 1. {
+                return None;
+            }
+            let corner = Vec2::new(offset.x + 1., 1. - offset.y) / 2. * RADAR_SIZE - size / 2.;
+            Some((corner, *size, *color))
+        });
+
+        for child in children.into_iter().flatten() {
+            let (mut style, mut background, mut visibility) = match radar.blips.get_mut(*child) {
+                Ok(blip) => blip,
+                Err(_) => continue,
+            };
+            match blips.next() {
+                Some((corner, size, color)) => {
+                    style.position.left = Val::Px(corner.x);
+                    style.position.top = Val::Px(corner.y);
+                    style.size = Size::new(Val::Px(size), Val::Px(size));
+                    background.0 = color;
+                    visibility.is_visible = true;
+                }
+                None => {
+                    if visibility.is_visible {
+                        visibility.is_visible = false;
+                    }
+                }
+            }
+        }
+        for (corner, size, color) in blips {
+            let blip = commands
+                .spawn(NodeBundle {
+                    style: blip_style(corner, size),
+                    background_color: color.into(),
+                    ..Default::default()
+                })
+                .insert(RadarBlip)
+                .id();
+            commands.entity(panel).add_child(blip);
+        }
+    }
+}
+
+fn blip_style(corner: Vec2, size: f32) -> Style {
+    Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+            left: Val::Px(corner.x),
+            top: Val::Px(corner.y),
+            ..Default::default()
+        },
+        size: Size::new(Val::Px(size), Val::Px(size)),
+        ..Default::default()
+    }
+}
+
+/// Pins a marker to the edge of the screen in the direction of every enemy out of view.
+fn edge_indicator_system(
+    mut commands: Commands,
+    settings: Res<RadarSettings>,
+    win_size: Res<WinSize>,
+    world_bounds: Res<WorldBounds>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    enemy_query: Query<&Transform, With<Enemy>>,
+    mut indicator_query: Query<(&mut Style, &mut Visibility), With<EdgeIndicator>>,
+) {
+    let mut markers = Vec::new();
+
+    if let Ok((camera_tf, projection)) = camera_query.get_single() {
+        let half_screen = Vec2::new(win_size.w, win_size.h) / 2.;
+        let inner = half_screen - EDGE_INDICATOR_INSET;
+        for enemy_tf in enemy_query.iter().filter(|_| settings.enabled) {
+            // in screen pixels from the middle of the window, y up
+            let offset = world_bounds.offset(camera_tf.translation.xy(), enemy_tf.translation.xy())
+                / projection.scale;
+            if offset.x.abs() <= half_screen.x && offset.y.abs() <= half_screen.y {
+                continue;
+            }
+            // slide in along the line to the enemy until it touches the inset edge
+            let scale = (inner.x / offset.x.abs()).min(inner.y / offset.y.abs());
+            let edge = offset * scale;
+            markers.push(
+                Vec2::new(half_screen.x + edge.x, half_screen.y - edge.y)
+                    - EDGE_INDICATOR_SIZE / 2.,
+            );
+        }
+    }
+
+    let mut markers = markers.into_iter();
+    for (mut style, mut visibility) in indicator_query.iter_mut() {
+        match markers.next() {
+            Some(corner) => {
+                style.position.left = Val::Px(corner.x);
+                style.position.top = Val::Px(corner.y);
+                visibility.is_visible = true;
+            }
+            None => {
+                if visibility.is_visible {
+                    visibility.is_visible = false;
+                }
+            }
+        }
+    }
+    for corner in markers {
+        commands
+            .spawn(NodeBundle {
+                style: blip_style(corner, EDGE_INDICATOR_SIZE),
+                background_color: Color::rgba(1., 0.2, 0.2, 0.8).into(),
+                ..Default::default()
+            })
+            .insert(EdgeIndicator);
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{
+    components::{ActivePowerUps, EnemyKind, Player, PlayerId, PowerUpKind},
+    EnemyCount, EnemyDestroyed, Players, COMBO_DECAY, COMBO_MAX, COMBO_STEP, COMBO_WINDOW,
+    SCORE_MULTIPLIER, SCORE_POPUP_LIFETIME,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Combos::default())
+            .insert_resource(WaveTracker::default())
+            .add_event::<ScoreEvent>()
+            .add_system(score_event_system)
+            .add_system(combo_decay_system.after(score_event_system))
+            .add_system(wave_bonus_system)
+            .add_system(score_popup_system);
+    }
+}
+
+/// Sent by anything that earns points, the score itself is only changed by `score_event_system`.
+pub struct ScoreEvent {
+    pub player: PlayerId, // who the points go to
+    pub reason: ScoreReason,
+    pub position: Vec3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScoreReason {
+    Kill(EnemyKind),
+    /// a laser hit an asteroid, at most once per `ASTEROID_HIT_INTERVAL` for each one
+    AsteroidHit,
+    NoDamageWave,
+    /// the player picked up speed from a close pass of a massive body
+    Slingshot,
+    /// a laser bent by gravity hit an enemy
+    TrickShot,
+    /// shot down another player in versus
+    PlayerKill,
+}
+
+impl ScoreReason {
+    /// points before the combo and power-up multipliers
+    pub fn points(&self) -> u32 {
+        match self {
+            ScoreReason::Kill(EnemyKind::Invader) => 100,
+            ScoreReason::AsteroidHit => 10,
+            ScoreReason::NoDamageWave => 500,
+            ScoreReason::Slingshot => 200,
+            ScoreReason::TrickShot => 250,
+            ScoreReason::PlayerKill => 300,
+        }
+    }
+}
+
+/// Grows with every scoring event and decays back to x1 once the player stops scoring.
+pub struct Combo {
+    pub multiplier: f32,
+    last_event: f64, // -1 if no event yet
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.,
+            last_event: -1.,
+        }
+    }
+}
+
+impl Combo {
+    fn bump(&mut self, time: f64) {
+        self.multiplier = (self.multiplier + COMBO_STEP).min(COMBO_MAX);
+        self.last_event = time;
+    }
+
+    /// once `COMBO_WINDOW` has passed without scoring, falls back towards x1
+    fn decay(&mut self, time: f64, dt: f32) {
+        if self.last_event != -1. && time > self.last_event + COMBO_WINDOW {
+            self.multiplier = (self.multiplier - COMBO_DECAY * dt).max(1.);
+        }
+    }
+}
+
+/// Each player builds their own combo.
+#[derive(Resource, Default)]
+pub struct Combos(HashMap<PlayerId, Combo>);
+
+impl Combos {
+    pub fn multiplier(&self, id: PlayerId) -> f32 {
+        self.0.get(&id).map_or(1., |combo| combo.multiplier)
+    }
+}
+
+/// A wave ends when the last enemy on screen is destroyed.
+#[derive(Resource, Default)]
+struct WaveTracker {
+    kills: u32,
+    damaged: HashSet<PlayerId>,
+    last_kill: Vec3,
+}
+
+#[derive(Component)]
+struct ScorePopup(Timer);
+
+fn score_event_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut players: ResMut<Players>,
+    mut combos: ResMut<Combos>,
+    mut score_events: EventReader<ScoreEvent>,
+    player_query: Query<(&PlayerId, &ActivePowerUps), With<Player>>,
+) {
+    for event in score_events.iter() {
+        let power_up_multiplier = match player_query.iter().find(|(id, _)| **id == event.player) {
+            Some((_, power_ups)) if power_ups.is_active(PowerUpKind::ScoreMultiplier) => {
+                SCORE_MULTIPLIER
+            }
+            _ => 1,
+        };
+
+        let combo = combos.0.entry(event.player).or_default();
+        let points =
+            (event.reason.points() as f32 * combo.multiplier).round() as u32 * power_up_multiplier;
+        players.state(event.player).score += points;
+        combo.bump(time.elapsed_seconds_f64());
+
+        // floating popup where the points were earned
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(
+                    format!("+{points}"),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Black.ttf"),
+                        font_size: 24.0,
+                        color: event.player.color(),
+                    },
+                )
+                .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_translation(event.position + Vec3::new(0., 0., 20.)),
+                ..Default::default()
+            })
+            .insert(ScorePopup(Timer::from_seconds(
+                SCORE_POPUP_LIFETIME,
+                TimerMode::Once,
+            )));
+    }
+}
+
+fn combo_decay_system(time: Res<Time>, players: Res<Players>, mut combos: ResMut<Combos>) {
+    for (id, combo) in combos.0.iter_mut() {
+        // dying loses the combo
+        if !players
+            .0
+            .get(id)
+            .is_some_and(|player_state| player_state.on)
+        {
+            combo.multiplier = 1.;
+            continue;
+        }
+
+        combo.decay(time.elapsed_seconds_f64(), time.delta_seconds());
+    }
+}
+
+/// Every player that got through the wave without dying earns the bonus.
+fn wave_bonus_system(
+    players: Res<Players>,
+    enemy_count: Res<EnemyCount>,
+    mut wave: ResMut<WaveTracker>,
+    mut enemy_destroyed: EventReader<EnemyDestroyed>,
+    mut score_events: EventWriter<ScoreEvent>,
+    mut last_seen: Local<HashMap<PlayerId, (f64, u32)>>,
+) {
+    for event in enemy_destroyed.iter() {
+        wave.kills += 1;
+        wave.last_kill = event.position;
+    }
+
+    // waiting to spawn is not damage, being shot or losing a life is
+    for (id, player_state) in players.0.iter() {
+        let seen = (player_state.last_shot, player_state.lives);
+        let (last_shot, lives) = *last_seen.entry(*id).or_insert(seen);
+        if (player_state.last_shot != last_shot && player_state.last_shot != -1.)
+            || player_state.lives < lives
+        {
+            wave.damaged.insert(*id);
+        }
+        last_seen.insert(*id, seen);
+    }
+
+    if enemy_count.0 == 0 && wave.kills > 0 {
+        for id in players.0.keys() {
+            if !wave.damaged.contains(id) {
+                score_events.send(ScoreEvent {
+                    player: *id,
+                    reason: ScoreReason::NoDamageWave,
+                    position: wave.last_kill,
+                });
+            }
+        }
+        *wave = WaveTracker::default();
+    }
+}
+
+fn score_popup_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut ScorePopup, &mut Transform, &mut Text)>,
+) {
+    for (entity, mut popup, mut transform, mut text) in query.iter_mut() {
+        popup.0.tick(time.delta());
+        if popup.0.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        // drift upwards while fading out
+        transform.translation.y += 40. * time.delta_seconds();
+        text.sections[0].style.color.set_a(1. - popup.0.percent());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_event_steps_the_combo_up_to_its_cap() {
+        let mut combo = Combo::default();
+        combo.bump(1.);
+        assert_eq!(combo.multiplier, 1. + COMBO_STEP);
+        for n in 0..100 {
+            combo.bump(1. + n as f64 * 0.1);
+        }
+        assert_eq!(combo.multiplier, COMBO_MAX);
+    }
+
+    #[test]
+    fn the_combo_holds_through_its_window_then_decays_to_one() {
+        let mut combo = Combo::default();
+        combo.bump(10.);
+        combo.bump(10.);
+        let built_up = combo.multiplier;
+
+        combo.decay(10. + COMBO_WINDOW, 0.5);
+        assert_eq!(combo.multiplier, built_up);
+
+        combo.decay(10.5 + COMBO_WINDOW, 0.5);
+        assert_eq!(combo.multiplier, built_up - COMBO_DECAY * 0.5);
+
+        // never below x1, however long it is left
+        combo.decay(100., 100.);
+        assert_eq!(combo.multiplier, 1.);
+    }
+
+    #[test]
+    fn a_fresh_combo_does_not_decay() {
+        let mut combo = Combo::default();
+        combo.decay(100., 1.);
+        assert_eq!(combo.multiplier, 1.);
+    }
+
+    /// A wave of `enemies`, with one player spawned and nothing killed yet.
+    fn wave(enemies: u32) -> App {
+        let mut app = App::new();
+        let mut players = Players::default();
+        let player_state = players.state(PlayerId(0));
+        player_state.lives = 3;
+        player_state.spawned();
+        app.insert_resource(players)
+            .insert_resource(EnemyCount(enemies))
+            .insert_resource(WaveTracker::default())
+            .add_event::<EnemyDestroyed>()
+            .add_event::<ScoreEvent>()
+            .add_system(wave_bonus_system);
+        app.update();
+        app
+    }
+
+    fn kill_enemy(app: &mut App) {
+        app.world.resource_mut::<EnemyCount>().0 -= 1;
+        app.world.send_event(EnemyDestroyed {
+            kind: EnemyKind::Invader,
+            position: Vec3::ZERO,
+        });
+        app.update();
+    }
+
+    fn bonuses(app: &App) -> usize {
+        let events = app.world.resource::<Events<ScoreEvent>>();
+        events
+            .get_reader()
+            .iter(events)
+            .filter(|event| event.reason == ScoreReason::NoDamageWave)
+            .count()
+    }
+
+    #[test]
+    fn clearing_a_wave_untouched_earns_the_bonus_once() {
+        let mut app = wave(2);
+        kill_enemy(&mut app);
+        assert_eq!(bonuses(&app), 0);
+        kill_enemy(&mut app);
+        assert_eq!(bonuses(&app), 1);
+        app.update();
+        app.update();
+        assert_eq!(bonuses(&app), 0);
+    }
+
+    #[test]
+    fn being_shot_during_the_wave_loses_the_bonus() {
+        let mut app = wave(2);
+        kill_enemy(&mut app);
+        app.world
+            .resource_mut::<Players>()
+            .state(PlayerId(0))
+            .shot(1.);
+        app.update();
+        // respawning clears the shot time, which is not more damage but does not undo it
+        app.world
+            .resource_mut::<Players>()
+            .state(PlayerId(0))
+            .spawned();
+        kill_enemy(&mut app);
+        assert_eq!(bonuses(&app), 0);
+
+        // the next wave starts over
+        app.world.resource_mut::<EnemyCount>().0 = 1;
+        kill_enemy(&mut app);
+        assert_eq!(bonuses(&app), 1);
+    }
+}
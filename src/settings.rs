@@ -0,0 +1,411 @@
+use crate::{
+    calibration::Calibration,
+    camera::CameraSettings,
+    controls::{
+        raw_stick, Action, Binding, ControlScheme, InputBindings, PauseState, Stick, ARROWS, WASD,
+    },
+    field::FieldOverlay,
+    haptics::HapticsSettings,
+    orbit::OrbitIntegrator,
+    player::{RotationGains, RotationModel},
+    radar::RadarSettings,
+    GameMode, WorldBounds,
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use std::marker::PhantomData;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SettingsMenu::default())
+            .add_startup_system(settings_menu_setup_system)
+            .add_system(settings_menu_toggle_system)
+            .add_system(settings_menu_navigation_system.after(settings_menu_toggle_system))
+            .add_system(settings_menu_capture_system.after(settings_menu_navigation_system))
+            .add_system(settings_menu_text_system.after(settings_menu_capture_system));
+    }
+}
+
+/// One line of the settings menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingsRow {
+    ControlScheme,
+    GameMode,
+    WorldEdge,
+    RotationModel,
+    // left and right adjust these
+    TurnStiffness,
+    TurnDamping,
+    AngularDamping,
+    CameraZoom,
+    ScreenShake,
+    Radar,
+    RadarRange,
+    GravityField,
+    Orbits,
+    Rumble,
+    RumbleIntensity,
+    CalibrateSticks,
+    Binding(Action),
+}
+
+impl SettingsRow {
+    fn all() -> Vec<SettingsRow> {
+        let mut rows = vec![
+            SettingsRow::ControlScheme,
+            SettingsRow::GameMode,
+            SettingsRow::WorldEdge,
+            SettingsRow::RotationModel,
+            SettingsRow::TurnStiffness,
+            SettingsRow::TurnDamping,
+            SettingsRow::AngularDamping,
+            SettingsRow::CameraZoom,
+            SettingsRow::ScreenShake,
+            SettingsRow::Radar,
+            SettingsRow::RadarRange,
+            SettingsRow::GravityField,
+            SettingsRow::Orbits,
+            SettingsRow::Rumble,
+            SettingsRow::RumbleIntensity,
+            SettingsRow::CalibrateSticks,
+        ];
+        rows.extend(
+            Action::ALL
+                .iter()
+                .map(|action| SettingsRow::Binding(*action)),
+        );
+        rows
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SettingsMenu {
+    pub open: bool,
+    selected: usize,
+    listening: bool,
+    /// set on the frame listening starts, so the press that started it is not bound
+    started_listening: bool,
+}
+
+#[derive(Component)]
+struct SettingsMenuText;
+
+fn settings_menu_setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Black.ttf"),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(20.0),
+                    left: Val::Percent(30.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        )
+        .insert(Visibility::INVISIBLE)
+        .insert(SettingsMenuText);
+}
+
+/// F1 opens and closes the menu, the game stays paused while it is open.
+fn settings_menu_toggle_system(
+    kb: Res<Input<KeyCode>>,
+    calibration: Res<Calibration>,
+    mut menu: ResMut<SettingsMenu>,
+    mut pause_state: ResMut<PauseState>,
+) {
+    if kb.just_pressed(KeyCode::F1) && !menu.listening && !calibration.open {
+        menu.open = !menu.open;
+        pause_state.in_menu = menu.open;
+    }
+}
+
+/// Everything the menu rows change.
+#[derive(SystemParam)]
+pub struct GameSettingsMut<'w, 's> {
+    scheme: ResMut<'w, ControlScheme>,
+    game_mode: ResMut<'w, GameMode>,
+    world_bounds: ResMut<'w, WorldBounds>,
+    rotation_model: ResMut<'w, RotationModel>,
+    gains: ResMut<'w, RotationGains>,
+    camera: ResMut<'w, CameraSettings>,
+    radar: ResMut<'w, RadarSettings>,
+    field_overlay: ResMut<'w, FieldOverlay>,
+    orbit_integrator: ResMut<'w, OrbitIntegrator>,
+    haptics: ResMut<'w, HapticsSettings>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+pub fn settings_menu_navigation_system(
+    kb: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut menu: ResMut<SettingsMenu>,
+    mut calibration: ResMut<Calibration>,
+    settings: GameSettingsMut,
+) {
+    if !menu.open || menu.listening || calibration.open {
+        return;
+    }
+
+    // any player can drive the menu
+    let pad_pressed = |button_type| {
+        buttons
+            .get_just_pressed()
+            .any(|button| button.button_type == button_type)
+    };
+
+    let rows = SettingsRow::all().len();
+    if kb.just_pressed(KeyCode::Down) || pad_pressed(GamepadButtonType::DPadDown) {
+        menu.selected = (menu.selected + 1) % rows;
+    }
+    if kb.just_pressed(KeyCode::Up) || pad_pressed(GamepadButtonType::DPadUp) {
+        menu.selected = (menu.selected + rows - 1) % rows;
+    }
+
+    let mut step = 0.;
+    if kb.just_pressed(KeyCode::Right) || pad_pressed(GamepadButtonType::DPadRight) {
+        step += 1.;
+    }
+    if kb.just_pressed(KeyCode::Left) || pad_pressed(GamepadButtonType::DPadLeft) {
+        step -= 1.;
+    }
+    let confirm = kb.just_pressed(KeyCode::Return) || pad_pressed(GamepadButtonType::South);
+
+    let GameSettingsMut {
+        mut scheme,
+        mut game_mode,
+        mut world_bounds,
+        mut rotation_model,
+        mut gains,
+        mut camera,
+        mut radar,
+        mut field_overlay,
+        mut orbit_integrator,
+        mut haptics,
+        ..
+    } = settings;
+    match SettingsRow::all()[menu.selected] {
+        SettingsRow::ControlScheme if confirm => *scheme = scheme.next(),
+        SettingsRow::GameMode if confirm => *game_mode = game_mode.next(),
+        SettingsRow::WorldEdge if confirm => world_bounds.mode = world_bounds.mode.next(),
+        SettingsRow::RotationModel if confirm => *rotation_model = rotation_model.next(),
+        SettingsRow::TurnStiffness if step != 0. => {
+            gains.stiffness = (gains.stiffness + step * 10.).clamp(10., 200.)
+        }
+        SettingsRow::TurnDamping if step != 0. => {
+            gains.damping = (gains.damping + step).clamp(0., 40.)
+        }
+        SettingsRow::AngularDamping if step != 0. => {
+            gains.angular_damping = (gains.angular_damping + step * 0.5).clamp(0., 10.)
+        }
+        SettingsRow::CameraZoom if confirm => camera.zoom_mode = camera.zoom_mode.next(),
+        SettingsRow::ScreenShake if confirm => camera.shake = !camera.shake,
+        SettingsRow::Radar if confirm => radar.enabled = !radar.enabled,
+        SettingsRow::RadarRange if step != 0. => {
+            radar.range = (radar.range + step * 500.).clamp(500., 10_000.)
+        }
+        SettingsRow::GravityField if confirm => *field_overlay = field_overlay.next(),
+        SettingsRow::Orbits if confirm => *orbit_integrator = orbit_integrator.next(),
+        SettingsRow::Rumble if confirm => haptics.enabled = !haptics.enabled,
+        SettingsRow::RumbleIntensity if step != 0. => {
+            haptics.intensity = (haptics.intensity + step * 0.1).clamp(0., 1.)
+        }
+        SettingsRow::CalibrateSticks if confirm => calibration.open = true,
+        SettingsRow::Binding(_) if confirm => {
+            menu.listening = true;
+            menu.started_listening = true;
+        }
+        _ => {}
+    }
+}
+
+/// Waits for the next key, mouse button, pad button or stick push and binds it to
+/// the selected action, replacing the old binding on the same kind of device.
+fn settings_menu_capture_system(
+    kb: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    mut menu: ResMut<SettingsMenu>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    if !menu.listening {
+        return;
+    }
+    // skip the frame the menu started listening on, or the confirm press gets bound
+    if menu.started_listening {
+        menu.started_listening = false;
+        return;
+    }
+    let action = match SettingsRow::all()[menu.selected] {
+        SettingsRow::Binding(action) => action,
+        _ => return,
+    };
+
+    // escape backs out without changing anything
+    if kb.just_pressed(KeyCode::Escape) {
+        menu.listening = false;
+        return;
+    }
+
+    let captured = if action.is_axis() {
+        let stick_pushed = |stick| {
+            gamepads
+                .iter()
+                .any(|gamepad| raw_stick(&axes, gamepad, stick).length() > 0.5)
+        };
+        let pressed_any = |keys: [KeyCode; 4]| keys.iter().any(|key| kb.just_pressed(*key));
+
+        if pressed_any([KeyCode::W, KeyCode::A, KeyCode::S, KeyCode::D]) {
+            Some(WASD)
+        } else if pressed_any([KeyCode::Up, KeyCode::Left, KeyCode::Down, KeyCode::Right]) {
+            Some(ARROWS)
+        } else if stick_pushed(Stick::Left) {
+            Some(Binding::LeftStick)
+        } else if stick_pushed(Stick::Right) {
+            Some(Binding::RightStick)
+        } else {
+            None
+        }
+    } else {
+        kb.get_just_pressed()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| {
+                mouse
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Mouse(*button))
+            })
+            .or_else(|| {
+                buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::GamepadButton(button.button_type))
+            })
+    };
+
+    if let Some(binding) = captured {
+        bindings.rebind(action, binding);
+        bindings.save();
+        menu.listening = false;
+    }
+}
+
+/// Everything the menu rows show.
+#[derive(SystemParam)]
+struct GameSettings<'w, 's> {
+    scheme: Res<'w, ControlScheme>,
+    game_mode: Res<'w, GameMode>,
+    world_bounds: Res<'w, WorldBounds>,
+    rotation_model: Res<'w, RotationModel>,
+    gains: Res<'w, RotationGains>,
+    camera: Res<'w, CameraSettings>,
+    radar: Res<'w, RadarSettings>,
+    field_overlay: Res<'w, FieldOverlay>,
+    orbit_integrator: Res<'w, OrbitIntegrator>,
+    haptics: Res<'w, HapticsSettings>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl GameSettings<'_, '_> {
+    fn is_changed(&self) -> bool {
+        self.scheme.is_changed()
+            || self.game_mode.is_changed()
+            || self.world_bounds.is_changed()
+            || self.rotation_model.is_changed()
+            || self.gains.is_changed()
+            || self.camera.is_changed()
+            || self.radar.is_changed()
+            || self.field_overlay.is_changed()
+            || self.orbit_integrator.is_changed()
+            || self.haptics.is_changed()
+    }
+}
+
+fn settings_menu_text_system(
+    menu: Res<SettingsMenu>,
+    bindings: Res<InputBindings>,
+    settings: GameSettings,
+    calibration: Res<Calibration>,
+    mut query: Query<(&mut Text, &mut Visibility), With<SettingsMenuText>>,
+) {
+    if !menu.is_changed()
+        && !bindings.is_changed()
+        && !settings.is_changed()
+        && !calibration.is_changed()
+    {
+        return;
+    }
+    let GameSettings {
+        scheme,
+        game_mode,
+        world_bounds,
+        rotation_model,
+        gains,
+        camera,
+        radar,
+        field_overlay,
+        orbit_integrator,
+        haptics,
+        ..
+    } = settings;
+
+    let mut lines = vec!["SETTINGS (F1 to close)".to_string(), String::new()];
+    for (i, row) in SettingsRow::all().iter().enumerate() {
+        let cursor = if i == menu.selected { "> " } else { "  " };
+        let value = match row {
+            SettingsRow::ControlScheme => format!("Control scheme: {}", scheme.label()),
+            SettingsRow::GameMode => format!("Game mode: {}", game_mode.label()),
+            SettingsRow::WorldEdge => format!("World edge: {}", world_bounds.mode.label()),
+            SettingsRow::RotationModel => format!("Rotation: {}", rotation_model.label()),
+            SettingsRow::TurnStiffness => format!("  Turn stiffness: < {:.0} >", gains.stiffness),
+            SettingsRow::TurnDamping => format!("  Turn damping: < {:.0} >", gains.damping),
+            SettingsRow::AngularDamping => {
+                format!("  Angular damping: < {:.1} >", gains.angular_damping)
+            }
+            SettingsRow::CameraZoom => format!("Camera zoom: {}", camera.zoom_mode.label()),
+            SettingsRow::ScreenShake => {
+                format!("Screen shake: {}", if camera.shake { "on" } else { "off" })
+            }
+            SettingsRow::Radar => format!("Radar: {}", if radar.enabled { "on" } else { "off" }),
+            SettingsRow::RadarRange => format!("  Radar range: < {:.0} >", radar.range),
+            SettingsRow::GravityField => format!("Gravity field (F2): {}", field_overlay.label()),
+            SettingsRow::Orbits => format!("Asteroid orbits: {}", orbit_integrator.label()),
+            SettingsRow::Rumble => {
+                format!("Rumble: {}", if haptics.enabled { "on" } else { "off" })
+            }
+            SettingsRow::RumbleIntensity => {
+                format!("Rumble intensity: < {:.0}% >", haptics.intensity * 100.)
+            }
+            SettingsRow::CalibrateSticks => "Calibrate sticks...".to_string(),
+            SettingsRow::Binding(action) if i == menu.selected && menu.listening => {
+                format!("{}: press a key or button...", action.label())
+            }
+            SettingsRow::Binding(action) => {
+                let labels: Vec<String> =
+                    bindings.get(*action).iter().map(Binding::label).collect();
+                format!("{}: {}", action.label(), labels.join(", "))
+            }
+        };
+        lines.push(format!("{cursor}{value}"));
+    }
+
+    for (mut text, mut visibility) in query.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+        // the calibration screen takes over while it is open
+        visibility.is_visible = menu.open && !calibration.open;
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    apply_gravitational_forces,
+    components::{Asteroid, GravityAssist, GravitySource, PlayerId},
+    score::{ScoreEvent, ScoreReason},
+    WorldBounds, EXTRA_GRAVITY, G, GRAVITY_SOURCE_MIN_DISTANCE, SLINGSHOT_MIN_ACCELERATION,
+    SLINGSHOT_MIN_DELTA_V,
+};
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_rapier2d::prelude::{ReadMassProperties, Velocity};
+
+pub struct SlingshotPlugin;
+
+impl Plugin for SlingshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(gravity_assist_system.after(apply_gravitational_forces));
+    }
+}
+
+/// Follows each tracked body through its close passes of asteroids and gravity
+/// sources. During a pass the component of the dominant body's pull along the
+/// direction of travel is integrated, giving the delta-v owed to that body.
+fn gravity_assist_system(
+    time: Res<Time>,
+    world_bounds: Res<WorldBounds>,
+    mut score_events: EventWriter<ScoreEvent>,
+    mut tracked_query: Query<(
+        Entity,
+        &Transform,
+        &Velocity,
+        &mut GravityAssist,
+        Option<&PlayerId>, // only ships score, lasers are tracked for trick shots
+    )>,
+    body_query: Query<(Entity, &Transform, &ReadMassProperties), With<Asteroid>>,
+    source_query: Query<(Entity, &Transform, &GravitySource)>,
+) {
+    let dt = time.delta_seconds();
+
+    // every massive body as (entity, position, mass)
+    let bodies: Vec<(Entity, Vec2, f32)> = body_query
+        .iter()
+        .map(|(ent, tf, mass_prop)| (ent, tf.translation.xy(), mass_prop.0.mass))
+        .chain(
+            source_query
+                .iter()
+                .map(|(ent, tf, source)| (ent, tf.translation.xy(), source.mass)),
+        )
+        .collect();
+
+    for (entity, transform, velocity, mut assist, player) in tracked_query.iter_mut() {
+        let position = transform.translation.xy();
+        let direction = velocity.linvel.normalize_or_zero();
+
+        // how far gravity has bent the path since launch
+        if direction != Vec2::ZERO {
+            match assist.launch_direction {
+                Some(launch_direction) => {
+                    assist.bend = launch_direction.angle_between(direction).abs()
+                }
+                None => assist.launch_direction = Some(direction),
+            }
+        }
+
+        // the body pulling hardest, with its acceleration and distance
+        let dominant = bodies
+            .iter()
+            .filter(|(body, ..)| *body != entity)
+            .map(|&(body, body_position, mass)| {
+                let offset = world_bounds.offset(position, body_position);
+                let distance_squared = offset
+                    .length_squared()
+                    .max(GRAVITY_SOURCE_MIN_DISTANCE.powi(2));
+                let acceleration =
+                    offset.normalize_or_zero() * mass * G * EXTRA_GRAVITY / distance_squared;
+                (body, acceleration, offset.length())
+            })
+            .max_by(|(_, a, _), (_, b, _)| a.length_squared().total_cmp(&b.length_squared()));
+
+        let passing = match dominant {
+            Some((body, acceleration, distance))
+                if acceleration.length() >= SLINGSHOT_MIN_ACCELERATION =>
+            {
+                Some((body, acceleration, distance))
+            }
+            _ => None,
+        };
+
+        let finished = track_pass(&mut assist, passing, direction, dt);
+        let slingshot = finished.is_some_and(|delta_v| delta_v >= SLINGSHOT_MIN_DELTA_V);
+        if let Some(player) = player.filter(|_| slingshot) {
+            score_events.send(ScoreEvent {
+                player: *player,
+                reason: ScoreReason::Slingshot,
+                position: transform.translation,
+            });
+        }
+    }
+}
+
+/// Carries a pass on by a frame of travelling along `direction`, with `passing` the body
+/// pulling hard enough to count as (body, acceleration, distance). Gives the delta-v of
+/// a pass that has just ended.
+fn track_pass(
+    assist: &mut GravityAssist,
+    passing: Option<(Entity, Vec2, f32)>,
+    direction: Vec2,
+    dt: f32,
+) -> Option<f32> {
+    let mut finished = None;
+
+    // a pass ends when the body lets go or another one takes over
+    if assist.body.is_some() && assist.body != passing.map(|(body, ..)| body) {
+        finished = Some(assist.delta_v);
+        assist.body = None;
+    }
+
+    if let Some((body, acceleration, distance)) = passing {
+        if assist.body.is_none() {
+            assist.body = Some(body);
+            assist.periapsis = distance;
+            assist.delta_v = 0.;
+        }
+        assist.periapsis = assist.periapsis.min(distance);
+        assist.delta_v += acceleration.dot(direction) * dt;
+    }
+
+    finished
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_pull_along_the_path_counts_towards_delta_v() {
+        let mut assist = GravityAssist::default();
+        let body = Entity::from_raw(1);
+        // ahead, sideways, then ahead and to the side
+        for acceleration in [Vec2::new(40., 0.), Vec2::new(0., 40.), Vec2::new(30., -20.)] {
+            track_pass(&mut assist, Some((body, acceleration, 100.)), Vec2::X, 0.5);
+        }
+        assert_eq!(assist.delta_v, 35.);
+
+        // pulled back on the way out
+        track_pass(
+            &mut assist,
+            Some((body, Vec2::new(-10., 0.), 100.)),
+            Vec2::X,
+            0.5,
+        );
+        assert_eq!(assist.delta_v, 30.);
+        assert_eq!(track_pass(&mut assist, None, Vec2::X, 0.5), Some(30.));
+        assert_eq!(assist.body, None);
+    }
+
+    #[test]
+    fn the_periapsis_is_the_closest_the_pass_came() {
+        let mut assist = GravityAssist::default();
+        let body = Entity::from_raw(1);
+        for distance in [300., 120., 80., 150.] {
+            track_pass(&mut assist, Some((body, Vec2::X, distance)), Vec2::X, 0.1);
+        }
+        assert_eq!(assist.periapsis, 80.);
+    }
+
+    #[test]
+    fn another_body_taking_over_starts_a_new_pass() {
+        let mut assist = GravityAssist::default();
+        let (first, second) = (Entity::from_raw(1), Entity::from_raw(2));
+
+        assert_eq!(
+            track_pass(&mut assist, Some((first, Vec2::X * 50., 60.)), Vec2::X, 1.),
+            None
+        );
+        let finished = track_pass(
+            &mut assist,
+            Some((second, Vec2::X * 20., 200.)),
+            Vec2::X,
+            1.,
+        );
+        assert_eq!(finished, Some(50.));
+        assert_eq!(assist.body, Some(second));
+        assert_eq!(assist.delta_v, 20.);
+        assert_eq!(assist.periapsis, 200.);
+
+        // nothing to hand in while no pass is going on
+        track_pass(&mut assist, None, Vec2::X, 1.);
+        assert_eq!(track_pass(&mut assist, None, Vec2::X, 1.), None);
+    }
+}
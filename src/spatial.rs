@@ -0,0 +1,377 @@
+use crate::{
+    components::{Enemy, FromEnemy, FromPlayer, Laser, Pickup, Player, SpriteSize},
+    telemetry::{SystemTimings, Timed},
+    BoundaryMode, WorldBounds, PICKUP_RADIUS, SPATIAL_CELL_SIZE,
+};
+use bevy::{math::Vec3Swizzles, prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::ExternalImpulse;
+
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpatialIndex::default())
+            // where everything ended up after last frame's physics, before anything looks
+            .add_system_to_stage(CoreStage::PreUpdate, spatial_index_system);
+    }
+}
+
+/// What an entry in the index is, queries only ever look at one layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpatialLayer {
+    Player,
+    Enemy,
+    PlayerLaser,
+    EnemyLaser,
+    Pickup,
+    /// anything else a blast can knock back
+    Body,
+}
+
+impl SpatialLayer {
+    pub const ALL: [SpatialLayer; 6] = [
+        SpatialLayer::Player,
+        SpatialLayer::Enemy,
+        SpatialLayer::PlayerLaser,
+        SpatialLayer::EnemyLaser,
+        SpatialLayer::Pickup,
+        SpatialLayer::Body,
+    ];
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpatialEntry {
+    pub entity: Entity,
+    pub position: Vec2,
+    /// full width and height of the box, zero for a point
+    pub size: Vec2,
+}
+
+impl SpatialEntry {
+    /// true when the boxes overlap, touching edges do not count, as with `collide`
+    pub fn overlaps(&self, rect: Rect, bounds: &WorldBounds) -> bool {
+        let gap = bounds.offset(rect.center(), self.position).abs() * 2.;
+        let size = self.size + rect.size();
+        gap.x < size.x && gap.y < size.y
+    }
+}
+
+/// A spatial hash of the gameplay entities, rebuilt at the start of every frame so
+/// hit checks, targeting and blasts only look at what is nearby. Entries are filed under
+/// the cell their centre is in, so lookups widen by the largest box in the layer.
+/// When the world wraps the cells tile it exactly and lookups carry on across the seams.
+/// Anything spawned during a frame shows up the frame after.
+#[derive(Resource, Default)]
+pub struct SpatialIndex {
+    entries: Vec<SpatialEntry>,
+    cells: HashMap<(SpatialLayer, IVec2), Vec<usize>>,
+    half_size: [Vec2; SpatialLayer::ALL.len()],
+    bounds: WorldBounds,
+}
+
+impl SpatialIndex {
+    /// Empties the index, ready to be filled for a world with these bounds.
+    pub fn clear(&mut self, bounds: &WorldBounds) {
+        self.entries.clear();
+        self.cells.clear();
+        self.half_size = Default::default();
+        self.bounds = *bounds;
+    }
+
+    pub fn insert(&mut self, layer: SpatialLayer, entity: Entity, position: Vec2, size: Vec2) {
+        self.cells
+            .entry((layer, self.key(self.cell(position))))
+            .or_default()
+            .push(self.entries.len());
+        self.entries.push(SpatialEntry {
+            entity,
+            position,
+            size,
+        });
+        let half_size = &mut self.half_size[layer as usize];
+        *half_size = half_size.max(size / 2.);
+    }
+
+    /// Entries in the layer whose box overlaps `rect`.
+    pub fn overlapping(
+        &self,
+        layer: SpatialLayer,
+        rect: Rect,
+    ) -> impl Iterator<Item = &SpatialEntry> + '_ {
+        self.near(layer, rect.min, rect.max)
+            .filter(move |entry| entry.overlaps(rect, &self.bounds))
+    }
+
+    /// Entries in the layer with their centre no further than `radius` from `centre`.
+    pub fn within_radius(
+        &self,
+        layer: SpatialLayer,
+        centre: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = &SpatialEntry> + '_ {
+        // centres are all that count, so there is no need to widen by the boxes
+        let reach = Vec2::splat(radius);
+        self.cells_between(layer, centre - reach, centre + reach)
+            .filter(move |entry| self.distance_squared(centre, entry.position) <= radius * radius)
+    }
+
+    /// The closest entry in the layer within `radius` that `filter` accepts.
+    pub fn nearest(
+        &self,
+        layer: SpatialLayer,
+        centre: Vec2,
+        radius: f32,
+        filter: impl Fn(&SpatialEntry) -> bool,
+    ) -> Option<&SpatialEntry> {
+        self.within_radius(layer, centre, radius)
+            .filter(|entry| filter(entry))
+            .min_by(|a, b| {
+                let a = self.distance_squared(centre, a.position);
+                let b = self.distance_squared(centre, b.position);
+                a.total_cmp(&b)
+            })
+    }
+
+    /// Everything whose box could reach into the area between `min` and `max`.
+    fn near(
+        &self,
+        layer: SpatialLayer,
+        min: Vec2,
+        max: Vec2,
+    ) -> impl Iterator<Item = &SpatialEntry> + '_ {
+        let half_size = self.half_size[layer as usize];
+        self.cells_between(layer, min - half_size, max + half_size)
+    }
+
+    fn cells_between(
+        &self,
+        layer: SpatialLayer,
+        min: Vec2,
+        max: Vec2,
+    ) -> impl Iterator<Item = &SpatialEntry> + '_ {
+        let (min, mut max) = (self.cell(min), self.cell(max));
+        if self.bounds.mode == BoundaryMode::Wrap {
+            // no further than once round, or the same cells come up twice
+            max = max.min(min + self.cells_across() - IVec2::ONE);
+        }
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(move |cell| self.cells.get(&(layer, self.key(cell))))
+            .flatten()
+            .map(|&index| &self.entries[index])
+    }
+
+    fn distance_squared(&self, from: Vec2, to: Vec2) -> f32 {
+        self.bounds.offset(from, to).length_squared()
+    }
+
+    fn cells_across(&self) -> IVec2 {
+        (self.bounds.size / SPATIAL_CELL_SIZE)
+            .ceil()
+            .max(Vec2::ONE)
+            .as_ivec2()
+    }
+
+    fn cell(&self, position: Vec2) -> IVec2 {
+        let cell_size = match self.bounds.mode {
+            // a little under the usual size, so a whole number of cells spans the world
+            BoundaryMode::Wrap => self.bounds.size / self.cells_across().as_vec2(),
+            _ => Vec2::splat(SPATIAL_CELL_SIZE),
+        };
+        (position / cell_size).floor().as_ivec2()
+    }
+
+    /// The cell an entry in `cell` is filed under, the same cell once round the world.
+    fn key(&self, cell: IVec2) -> IVec2 {
+        match self.bounds.mode {
+            BoundaryMode::Wrap => {
+                let across = self.cells_across();
+                IVec2::new(cell.x.rem_euclid(across.x), cell.y.rem_euclid(across.y))
+            }
+            _ => cell,
+        }
+    }
+}
+
+/// The box a sprite covers, as the hit checks see it.
+pub fn sprite_rect(tf: &Transform, size: &SpriteSize) -> Rect {
+    Rect::from_center_size(tf.translation.xy(), size.0 * tf.scale.xy())
+}
+
+/// The components that decide which layer a sprite goes in.
+type SpriteLayer = (
+    Option<&'static Player>,
+    Option<&'static Enemy>,
+    Option<&'static Laser>,
+    Option<&'static FromPlayer>,
+    Option<&'static FromEnemy>,
+);
+
+/// Anything else a blast can push, ships and enemies are caught on their own layers.
+type KnockedBack = (With<ExternalImpulse>, Without<Player>, Without<Enemy>);
+
+pub fn spatial_index_system(
+    world_bounds: Res<WorldBounds>,
+    mut index: ResMut<SpatialIndex>,
+    sprite_query: Query<(Entity, &Transform, &SpriteSize, SpriteLayer)>,
+    pickup_query: Query<(Entity, &Transform), With<Pickup>>,
+    body_query: Query<(Entity, &Transform), KnockedBack>,
+    timings: Res<SystemTimings>,
+) {
+    let _timer = timings.start(Timed::Collision);
+    index.clear(&world_bounds);
+
+    for (entity, tf, size, kind) in sprite_query.iter() {
+        let layer = match kind {
+            (Some(_), ..) => SpatialLayer::Player,
+            (_, Some(_), ..) => SpatialLayer::Enemy,
+            (_, _, Some(_), Some(_), _) => SpatialLayer::PlayerLaser,
+            (_, _, Some(_), _, Some(_)) => SpatialLayer::EnemyLaser,
+            _ => continue,
+        };
+        let rect = sprite_rect(tf, size);
+        index.insert(layer, entity, rect.center(), rect.size());
+    }
+    for (entity, tf) in pickup_query.iter() {
+        index.insert(
+            SpatialLayer::Pickup,
+            entity,
+            tf.translation.xy(),
+            Vec2::splat(PICKUP_RADIUS * 2.),
+        );
+    }
+    for (entity, tf) in body_query.iter() {
+        index.insert(SpatialLayer::Body, entity, tf.translation.xy(), Vec2::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(mode: BoundaryMode, entries: &[(u32, Vec2, Vec2)]) -> SpatialIndex {
+        let bounds = WorldBounds {
+            size: Vec2::new(1000., 800.),
+            mode,
+        };
+        let mut index = SpatialIndex::default();
+        index.clear(&bounds);
+        for (id, position, size) in entries {
+            index.insert(SpatialLayer::Enemy, Entity::from_raw(*id), *position, *size);
+        }
+        index
+    }
+
+    fn ids<'a>(entries: impl Iterator<Item = &'a SpatialEntry>) -> Vec<u32> {
+        let mut ids: Vec<u32> = entries.map(|entry| entry.entity.index()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn overlapping_reaches_boxes_filed_in_the_next_cell() {
+        // centred just past a cell edge, but wide enough to reach back over it
+        let edge = SPATIAL_CELL_SIZE;
+        let index = filled(
+            BoundaryMode::Soft,
+            &[
+                (1, Vec2::new(edge + 10., 0.), Vec2::splat(40.)),
+                (2, Vec2::new(edge + 30., 0.), Vec2::splat(10.)),
+            ],
+        );
+        let rect = Rect::from_center_size(Vec2::new(edge - 5., 0.), Vec2::splat(10.));
+        assert_eq!(ids(index.overlapping(SpatialLayer::Enemy, rect)), vec![1]);
+        // other layers are never looked at
+        assert_eq!(
+            ids(index.overlapping(SpatialLayer::Player, rect)),
+            Vec::<u32>::new()
+        );
+    }
+
+    #[test]
+    fn within_radius_counts_centres_either_side_of_a_cell_edge() {
+        let edge = SPATIAL_CELL_SIZE;
+        let index = filled(
+            BoundaryMode::Soft,
+            &[
+                (1, Vec2::new(edge - 20., 0.), Vec2::ZERO),
+                (2, Vec2::new(edge + 20., 0.), Vec2::ZERO),
+                (3, Vec2::new(edge + 60., 0.), Vec2::ZERO),
+            ],
+        );
+        let centre = Vec2::new(edge, 0.);
+        assert_eq!(
+            ids(index.within_radius(SpatialLayer::Enemy, centre, 30.)),
+            vec![1, 2]
+        );
+        assert_eq!(
+            ids(index.within_radius(SpatialLayer::Enemy, centre, 60.)),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn nearest_looks_past_the_cell_it_starts_in() {
+        let edge = SPATIAL_CELL_SIZE;
+        let index = filled(
+            BoundaryMode::Soft,
+            &[
+                (1, Vec2::new(edge - 50., 0.), Vec2::ZERO),
+                (2, Vec2::new(edge + 5., 0.), Vec2::ZERO),
+            ],
+        );
+        let centre = Vec2::new(edge - 10., 0.);
+        let nearest = |filter: fn(&SpatialEntry) -> bool| {
+            index
+                .nearest(SpatialLayer::Enemy, centre, 100., filter)
+                .map(|entry| entry.entity.index())
+        };
+        assert_eq!(nearest(|_| true), Some(2));
+        assert_eq!(nearest(|entry| entry.entity.index() != 2), Some(1));
+        assert_eq!(nearest(|_| false), None);
+    }
+
+    #[test]
+    fn wrapping_lookups_carry_on_across_the_seam() {
+        let index = filled(
+            BoundaryMode::Wrap,
+            &[
+                (1, Vec2::new(490., 0.), Vec2::splat(40.)),
+                (2, Vec2::new(-490., 390.), Vec2::ZERO),
+            ],
+        );
+        let centre = Vec2::new(-495., 0.);
+        assert_eq!(
+            ids(index.within_radius(SpatialLayer::Enemy, centre, 20.)),
+            vec![1]
+        );
+        let rect = Rect::from_center_size(centre, Vec2::splat(4.));
+        assert_eq!(ids(index.overlapping(SpatialLayer::Enemy, rect)), vec![1]);
+        // both seams at once, from one corner of the world to the opposite one
+        let nearest = index.nearest(SpatialLayer::Enemy, Vec2::new(495., -395.), 30., |_| true);
+        assert_eq!(nearest.map(|entry| entry.entity.index()), Some(2));
+
+        // the same lookup without wrapping only sees what is on its side
+        let walled = filled(BoundaryMode::Walls, &[(1, Vec2::new(490., 0.), Vec2::ZERO)]);
+        assert!(walled
+            .within_radius(SpatialLayer::Enemy, centre, 20.)
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn wrapping_lookups_wider_than_the_world_find_everything_once() {
+        let index = filled(
+            BoundaryMode::Wrap,
+            &[
+                (1, Vec2::new(-300., 100.), Vec2::ZERO),
+                (2, Vec2::new(450., -350.), Vec2::ZERO),
+            ],
+        );
+        let rect = Rect::from_center_size(Vec2::ZERO, Vec2::new(3000., 3000.));
+        assert_eq!(
+            ids(index.overlapping(SpatialLayer::Enemy, rect)),
+            vec![1, 2]
+        );
+    }
+}
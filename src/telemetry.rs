@@ -0,0 +1,260 @@
+use crate::{LaunchOptions, TELEMETRY_HISTORY, TELEMETRY_INTERVAL};
+use bevy::{
+    app::AppExit,
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_rapier2d::{
+    plugin::systems::step_simulation,
+    prelude::{NoUserData, PhysicsStages, RigidBody},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+/// Needs the rapier stages, so is added after `RapierPhysicsPlugin`.
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
+            .insert_resource(SystemTimings::default())
+            .add_startup_system(telemetry_setup_system)
+            .add_system_to_stage(
+                PhysicsStages::StepSimulation,
+                physics_timer_start_system.before(step_simulation::<NoUserData>),
+            )
+            .add_system_to_stage(
+                PhysicsStages::StepSimulation,
+                physics_timer_end_system.after(step_simulation::<NoUserData>),
+            )
+            // once every timed system has run for the frame
+            .add_system_to_stage(CoreStage::Last, telemetry_measure_system)
+            .add_system_to_stage(
+                CoreStage::Last,
+                telemetry_log_system.after(telemetry_measure_system),
+            )
+            .add_system_to_stage(CoreStage::Last, frame_limit_system);
+    }
+}
+
+/// The systems, or groups of them, whose time is measured every frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timed {
+    Gravity,
+    Collision,
+    Spawn,
+    Input,
+    /// rapier's `step_simulation`
+    Physics,
+}
+
+impl Timed {
+    pub const ALL: [Timed; 5] = [
+        Timed::Gravity,
+        Timed::Collision,
+        Timed::Spawn,
+        Timed::Input,
+        Timed::Physics,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Timed::Gravity => "gravity",
+            Timed::Collision => "collision",
+            Timed::Spawn => "spawn",
+            Timed::Input => "input",
+            Timed::Physics => "physics",
+        }
+    }
+
+    /// milliseconds spent in it each frame
+    pub fn diagnostic_id(&self) -> DiagnosticId {
+        match self {
+            Timed::Gravity => DiagnosticId::from_u128(0x6b2f_0c9a_41d3_4e0b_9a7e_53c1_0000_0001),
+            Timed::Collision => DiagnosticId::from_u128(0x6b2f_0c9a_41d3_4e0b_9a7e_53c1_0000_0002),
+            Timed::Spawn => DiagnosticId::from_u128(0x6b2f_0c9a_41d3_4e0b_9a7e_53c1_0000_0003),
+            Timed::Input => DiagnosticId::from_u128(0x6b2f_0c9a_41d3_4e0b_9a7e_53c1_0000_0004),
+            Timed::Physics => DiagnosticId::from_u128(0x6b2f_0c9a_41d3_4e0b_9a7e_53c1_0000_0005),
+        }
+    }
+}
+
+/// Number of rigid bodies in the world.
+pub const BODY_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0x6b2f_0c9a_41d3_4e0b_9a7e_53c1_0000_0010);
+
+/// Time spent in each `Timed` this frame, added to by the systems themselves. The
+/// nanoseconds are atomics so the timed systems only need `Res` and still run in parallel.
+#[derive(Resource, Default)]
+pub struct SystemTimings {
+    frame_nanos: [AtomicU64; Timed::ALL.len()],
+    physics_start: Option<Instant>,
+}
+
+impl SystemTimings {
+    pub fn record(&self, timed: Timed, start: Instant) {
+        let nanos = start.elapsed().as_nanos() as u64;
+        self.frame_nanos[timed as usize].fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Times the rest of the scope, including any early return.
+    pub fn start(&self, timed: Timed) -> ScopedTimer<'_> {
+        ScopedTimer {
+            timings: self,
+            timed,
+            start: Instant::now(),
+        }
+    }
+}
+
+pub struct ScopedTimer<'a> {
+    timings: &'a SystemTimings,
+    timed: Timed,
+    start: Instant,
+}
+
+impl Drop for ScopedTimer<'_> {
+    fn drop(&mut self) {
+        self.timings.record(self.timed, self.start);
+    }
+}
+
+/// Rolling stats written out every `TELEMETRY_INTERVAL` seconds of real time.
+#[derive(Resource)]
+struct TelemetryLog {
+    file: BufWriter<File>,
+    timer: Timer,
+}
+
+fn telemetry_setup_system(
+    mut commands: Commands,
+    options: Res<LaunchOptions>,
+    mut diagnostics: ResMut<Diagnostics>,
+) {
+    for timed in Timed::ALL {
+        diagnostics.add(
+            Diagnostic::new(timed.diagnostic_id(), timed.label(), TELEMETRY_HISTORY)
+                .with_suffix("ms"),
+        );
+    }
+    diagnostics.add(Diagnostic::new(BODY_COUNT, "bodies", TELEMETRY_HISTORY));
+
+    let path = match &options.telemetry {
+        Some(path) => path,
+        None => return,
+    };
+    let mut file = match File::create(path) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => {
+            error!("could not create {path}: {err}");
+            return;
+        }
+    };
+
+    let mut header = vec!["seconds", "fps", "frame_ms"];
+    let columns: Vec<String> = Timed::ALL
+        .iter()
+        .flat_map(|timed| {
+            [
+                format!("{}_avg_ms", timed.label()),
+                format!("{}_max_ms", timed.label()),
+            ]
+        })
+        .collect();
+    header.extend(columns.iter().map(String::as_str));
+    header.push("bodies");
+    if let Err(err) = writeln!(file, "{}", header.join(",")) {
+        error!("could not write to {path}: {err}");
+        return;
+    }
+
+    commands.insert_resource(TelemetryLog {
+        file,
+        timer: Timer::from_seconds(TELEMETRY_INTERVAL, TimerMode::Repeating),
+    });
+}
+
+// the physics pair has its stage to itself, so `ResMut` holds nothing else up
+fn physics_timer_start_system(mut timings: ResMut<SystemTimings>) {
+    timings.physics_start = Some(Instant::now());
+}
+
+fn physics_timer_end_system(mut timings: ResMut<SystemTimings>) {
+    if let Some(start) = timings.physics_start.take() {
+        timings.record(Timed::Physics, start);
+    }
+}
+
+fn telemetry_measure_system(
+    timings: Res<SystemTimings>,
+    mut diagnostics: ResMut<Diagnostics>,
+    body_query: Query<(), With<RigidBody>>,
+) {
+    for timed in Timed::ALL {
+        // read and reset in one go for the next frame
+        let nanos = timings.frame_nanos[timed as usize].swap(0, Ordering::Relaxed);
+        diagnostics.add_measurement(timed.diagnostic_id(), || nanos as f64 / 1_000_000.);
+    }
+    diagnostics.add_measurement(BODY_COUNT, || body_query.iter().count() as f64);
+}
+
+/// Quits once `--frames` frames have run.
+fn frame_limit_system(
+    options: Res<LaunchOptions>,
+    mut frames: Local<u32>,
+    mut exit: EventWriter<AppExit>,
+) {
+    *frames += 1;
+    if options.frames.is_some_and(|limit| *frames >= limit) {
+        exit.send(AppExit);
+    }
+}
+
+fn telemetry_log_system(
+    time: Res<Time>,
+    diagnostics: Res<Diagnostics>,
+    log: Option<ResMut<TelemetryLog>>,
+) {
+    let mut log = match log {
+        Some(log) => log,
+        None => return,
+    };
+    // real time, so the log carries on while the game is paused
+    if !log.timer.tick(time.raw_delta()).just_finished() {
+        return;
+    }
+
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or(0.)
+    };
+    let max = |id| {
+        diagnostics
+            .get(id)
+            .map(|diagnostic| diagnostic.values().copied().fold(0., f64::max))
+            .unwrap_or(0.)
+    };
+
+    let mut row = vec![
+        format!("{:.2}", time.raw_elapsed_seconds()),
+        format!("{:.1}", average(FrameTimeDiagnosticsPlugin::FPS)),
+        format!("{:.3}", average(FrameTimeDiagnosticsPlugin::FRAME_TIME)),
+    ];
+    for timed in Timed::ALL {
+        row.push(format!("{:.3}", average(timed.diagnostic_id())));
+        row.push(format!("{:.3}", max(timed.diagnostic_id())));
+    }
+    row.push(format!("{:.0}", average(BODY_COUNT)));
+
+    // flushed every row so a run that is killed still leaves its stats behind
+    let written = writeln!(log.file, "{}", row.join(",")).and_then(|_| log.file.flush());
+    if let Err(err) = written {
+        error!("could not write telemetry: {err}");
+    }
+}